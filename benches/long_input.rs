@@ -0,0 +1,35 @@
+//! Benchmarks of parsing and evaluating very long input lines (run with `cargo bench`)
+
+#![feature(test)]
+
+extern crate test;
+extern crate ucalc;
+
+use test::Bencher;
+
+/// An expression of about 50,000 characters made by repeating `term`
+fn long_input(term: &str, last: &str) -> String {
+    let mut s = String::new();
+    while s.len() + last.len() < 50000 {
+        s.push_str(term);
+    }
+    s + last
+}
+
+#[bench]
+fn bench_long_sum(b: &mut Bencher) {
+    let input = long_input("1+", "1");
+    b.iter(|| ucalc::evaluate(&input));
+}
+
+#[bench]
+fn bench_long_product(b: &mut Bencher) {
+    let input = long_input("1 ", "2");
+    b.iter(|| ucalc::evaluate(&input));
+}
+
+#[bench]
+fn bench_long_number(b: &mut Bencher) {
+    let input = long_input("1", "");
+    b.iter(|| ucalc::parse_partial(&input));
+}
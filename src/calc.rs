@@ -0,0 +1,1493 @@
+//! Calculator module. A Calculator holds the settings of a session and evaluates expressions
+//! using those settings.
+
+use {Expression, CalculatorError, Function, arity_matches, error_position, with_stack, make_value, raw_input, parse_rpn, binding, shown_var, conversion, get_unit, get_numerical_constant, get_function};
+use value::{Value, ArithmeticError, InexactReason, DisplayMode, Precision};
+use uval::UnitValue;
+use unit::Unit;
+use rational::{Rational, RoundingMode, AsFloat};
+use numtheory;
+use units;
+use measurement::Measurement;
+use sigfig::{self, SigFigs};
+
+use nom::IResult;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Write};
+
+/// How arithmetic operators behave
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CalcMode {
+    /// Division of integers gives an exact fraction (7/2 = 7/2)
+    Normal,
+    /// Division of integers is truncated like a four-function calculator (7/2 = 3)
+    Integer,
+}
+
+/// The unit of angles given to and returned by trigonometric functions
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AngleMode {
+    /// sin(pi/2) = 1
+    Radians,
+    /// sin(90) = 1
+    Degrees,
+}
+
+/// A calculator session
+pub struct Calculator {
+    /// How arithmetic operators behave
+    pub mode: CalcMode,
+    /// The unit of angles in trigonometric functions
+    pub angles: AngleMode,
+    /// How exact results are displayed
+    pub display: DisplayMode,
+    /// How many digits decimal results are rounded to, and how halfway cases are rounded (also
+    /// by `round` and when rounding to significant figures)
+    pub precision: Precision,
+    /// Whether input is written in postfix (RPN), e.g. `3 4 +`, rather than infix
+    pub rpn: bool,
+    /// Exact values with a larger denominator than this become inexact
+    pub max_denominator: Option<u32>,
+    /// Whether units are checked; if not, all values are treated as unitless (in SI base units)
+    pub strict_units: bool,
+    /// The result of the last successful calculation
+    pub last_result: Option<UnitValue>,
+    /// Values of variables
+    pub variables: HashMap<String, UnitValue>,
+    /// The unit each variable bound with `:=` is displayed in (e.g. `W` for `power := 500 W`)
+    pub display_units: HashMap<String, String>,
+    /// Each successful line of input and its result, in order
+    pub history: Vec<(String, UnitValue)>,
+    /// Warnings about the last line (e.g. precision loss); cleared before each line
+    pub warnings: Vec<String>,
+    /// The target of the last line if it was a unit conversion (as written, and its value)
+    pub conversion: Option<(String, UnitValue)>,
+    /// The zero point of the target of the last line's conversion (in SI base units), if it
+    /// was a temperature conversion (e.g. 273.15 for degC)
+    pub conversion_zero: Option<Value>,
+    /// The variable the last line was bound to, if it was a binding (e.g. `area` for
+    /// `area = 50 m^2`)
+    pub binding: Option<String>,
+    /// The uncertainty of the last line, if it had one (e.g. `1/2` for `10 ± 0.5`)
+    pub uncertainty: Option<UnitValue>,
+    /// The unit system of the last line if it had units: that of the first unit written in it,
+    /// so e.g. `2 ft * 3 m` is shown in square feet, but `3 m * 2 ft` in square meters
+    pub system: Option<units::System>,
+    /// Each operation done while evaluating (e.g. `3 * 4 = 12`), if they are being recorded
+    pub steps: Option<Vec<String>>,
+    /// Functions registered with `register_function`, and how many arguments they take
+    pub functions: HashMap<String, (usize, Function)>,
+    /// Results whose unit has a higher degree than this (e.g. 6 for m^6) get a warning, since
+    /// they are often a mistake
+    pub max_degree: Option<u32>,
+    /// Where the syntax error in the last line was (as a byte offset), if it had one
+    pub error_position: Option<usize>,
+    /// Whether significant figures are tracked (results are then rounded for display)
+    pub track_sig_figs: bool,
+    /// The significant figures of the last line, if they are tracked and it had any inexact
+    /// numbers (exact values such as units and constants don't limit the precision)
+    pub sig_figs: Option<i32>,
+}
+
+impl Calculator {
+    /// Create a calculator with the default settings
+    pub fn new() -> Calculator {
+        Calculator {
+            mode: CalcMode::Normal,
+            angles: AngleMode::Radians,
+            display: DisplayMode::Fraction,
+            precision: Precision::default(),
+            rpn: false,
+            max_denominator: None,
+            strict_units: true,
+            last_result: None,
+            variables: HashMap::new(),
+            display_units: HashMap::new(),
+            history: Vec::new(),
+            warnings: Vec::new(),
+            conversion: None,
+            conversion_zero: None,
+            binding: None,
+            uncertainty: None,
+            system: None,
+            steps: None,
+            functions: HashMap::new(),
+            max_degree: None,
+            error_position: None,
+            track_sig_figs: false,
+            sig_figs: None,
+        }
+    }
+    /// Register a function that takes `arity` arguments (or any number, for `VARIADIC`), so it
+    /// can be called by name.
+    /// A registered function replaces a builtin function with the same name; a name that is
+    /// a unit or constant can't be used, since e.g. `m(2)` is a multiplication.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, f: F)
+            where F: Fn(Vec<UnitValue>) -> Result<UnitValue, ArithmeticError> + Send + 'static {
+        self.functions.insert(name.to_owned(), (arity, Box::new(f)));
+    }
+    /// Try to handle a line as a command (e.g. `calcmode integer`, or a short form such as
+    /// `:dec`, see `short_command`).
+    /// Returns None if the line is not a command, a message to print (possibly empty) if it is,
+    /// or an error message if the command is invalid.
+    pub fn command(&mut self, line: &str) -> Option<Result<String, String>> {
+        if line.starts_with(':') {
+            return short_command(line).and_then(|line| self.command(&line))
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("why-inexact"), None, None) => return Some(Ok(match self.last_result {
+                None => "no result yet".to_owned(),
+                Some(a) => match a.inexact_reason() {
+                    None => "the last result is exact".to_owned(),
+                    Some(InexactReason::FloatInput) => "a number could not be represented exactly".to_owned(),
+                    Some(InexactReason::Overflow) => "an exact calculation overflowed".to_owned(),
+                    Some(InexactReason::IrrationalFunction) => "a function or power gave an irrational result".to_owned(),
+                    Some(InexactReason::ExplicitFloat) => "an inexact constant was used".to_owned(),
+                    Some(InexactReason::LargeDenominator) => "a denominator was larger than maxden".to_owned(),
+                },
+            })),
+            // the last result as a reduced fraction in SI base units, whatever the display settings
+            (Some("normalize"), None, None) => return Some(Ok(match self.last_result {
+                None => "no result yet".to_owned(),
+                Some(a) => a.format(DisplayMode::Fraction),
+            })),
+            // the number of the last result alone (in SI base units), for copying or piping
+            (Some("raw"), None, None) => return Some(Ok(match self.last_result {
+                None => "no result yet".to_owned(),
+                Some(a) => a.value.format_with(self.display, self.precision),
+            })),
+            (Some("calcmode"), Some("normal"), None) => self.mode = CalcMode::Normal,
+            (Some("calcmode"), Some("integer"), None) => self.mode = CalcMode::Integer,
+            (Some("calcmode"), _, _) => return Some(Err("usage: calcmode normal|integer".to_owned())),
+            (Some("angles"), Some("radians"), None) => self.angles = AngleMode::Radians,
+            (Some("angles"), Some("degrees"), None) => self.angles = AngleMode::Degrees,
+            (Some("angles"), _, _) => return Some(Err("usage: angles radians|degrees".to_owned())),
+            (Some("display"), Some("fraction"), None) => self.display = DisplayMode::Fraction,
+            (Some("display"), Some("decimal"), None) => self.display = DisplayMode::Decimal,
+            (Some("display"), Some("repeating"), None) => self.display = DisplayMode::Repeating,
+            (Some("display"), Some("mixed"), None) => self.display = DisplayMode::Mixed,
+            // scientific notation, with all significant digits or rounded to N
+            (Some("display"), Some("scientific"), None) => {
+                self.display = DisplayMode::Scientific;
+                self.precision.scientific = None;
+            },
+            (Some("display"), Some("scientific"), Some(n)) if n.parse::<usize>().map(|n| n > 0).unwrap_or(false) => {
+                self.display = DisplayMode::Scientific;
+                self.precision.scientific = n.parse().ok();
+            },
+            (Some("display"), _, _) => return Some(Err("usage: display fraction|decimal|mixed|repeating|scientific [N]".to_owned())),
+            // the number of digits after the point in decimals; the display mode is kept
+            (Some("precision"), None, None) => self.precision.decimals = None,
+            (Some("precision"), Some(n), None) if n.parse::<usize>().is_ok() => self.precision.decimals = n.parse().ok(),
+            (Some("precision"), _, _) => return Some(Err("usage: precision [N]".to_owned())),
+            (Some("maxden"), Some("off"), None) => self.max_denominator = None,
+            (Some("maxden"), Some(n), None) if n.parse::<u32>().map(|n| n > 0).unwrap_or(false)
+                => self.max_denominator = n.parse().ok(),
+            (Some("maxden"), _, _) => return Some(Err("usage: maxden N|off".to_owned())),
+            (Some("units"), Some("strict"), None) => self.strict_units = true,
+            (Some("units"), Some("off"), None) => self.strict_units = false,
+            (Some("units"), _, _) => return Some(Err("usage: units strict|off".to_owned())),
+            (Some("maxdegree"), Some("off"), None) => self.max_degree = None,
+            (Some("maxdegree"), Some(n), None) if n.parse::<u32>().is_ok() => self.max_degree = n.parse().ok(),
+            (Some("maxdegree"), _, _) => return Some(Err("usage: maxdegree N|off".to_owned())),
+            (Some("sigfigs"), Some("on"), None) => self.track_sig_figs = true,
+            (Some("sigfigs"), Some("off"), None) => self.track_sig_figs = false,
+            (Some("sigfigs"), _, _) => return Some(Err("usage: sigfigs on|off".to_owned())),
+            (Some("rounding"), Some("half-even"), None) => self.precision.rounding = RoundingMode::HalfEven,
+            (Some("rounding"), Some("half-up"), None) => self.precision.rounding = RoundingMode::HalfUp,
+            (Some("rounding"), Some("half-away"), None) => self.precision.rounding = RoundingMode::HalfAway,
+            (Some("rounding"), _, _) => return Some(Err("usage: rounding half-even|half-up|half-away".to_owned())),
+            (Some("rpn"), Some("on"), None) => self.rpn = true,
+            (Some("rpn"), Some("off"), None) => self.rpn = false,
+            (Some("rpn"), _, _) => return Some(Err("usage: rpn on|off".to_owned())),
+            (Some("export"), Some(path), None) => return Some(
+                match File::create(path).and_then(|mut f| self.export_csv(&mut f)) {
+                    Ok(()) => Ok(format!("wrote {} results to {}", self.history.len(), path)),
+                    Err(e) => Err(format!("could not write {}: {}", path, e)),
+                }),
+            (Some("export"), _, _) => return Some(Err("usage: export FILE".to_owned())),
+            (Some("factor"), Some(_), _) => return Some(
+                match self.run_line(line["factor".len()..].trim()).map_err(|_| ()).and_then(|n| numtheory::positive_integer(&n).map_err(|_| ())) {
+                    Ok(n) => Ok(numtheory::format_factors(&numtheory::factorize(n))),
+                    Err(()) => Err("factor requires a positive integer".to_owned()),
+                }),
+            (Some("steps"), Some(_), _) => {
+                self.steps = Some(Vec::new());
+                let res = self.run_line(line["steps".len()..].trim());
+                let mut steps = self.steps.take().unwrap_or(Vec::new());
+                return Some(match res {
+                    // nothing to calculate (e.g. a single number)
+                    Ok(val) if steps.is_empty() => Ok(format!("{}", val)),
+                    Ok(_) => Ok(steps.join("\n")),
+                    Err(e) => {
+                        steps.push(format!("{}", e));
+                        Err(steps.join("\n"))
+                    },
+                })
+            },
+            _ => return None,
+        }
+        Some(Ok(String::new()))
+    }
+    /// Apply settings from a config file, where each line is a command (e.g. `angles degrees`).
+    /// Blank lines and lines starting with `#` are ignored. Returns a warning for each line that
+    /// is not a valid command; the other lines still take effect.
+    pub fn load_config<R: BufRead>(&mut self, input: R) -> io::Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = try!(line);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            match self.command(line) {
+                Some(Ok(_)) => (),
+                Some(Err(msg)) => warnings.push(format!("line {}: {}", i + 1, msg)),
+                None => warnings.push(format!("line {}: unknown setting {}", i + 1, line)),
+            }
+        }
+        Ok(warnings)
+    }
+    /// Parse and evaluate a line of input, which is either an expression or a binding
+    /// (`name := expression` or `name = expression`). Successful lines are added to the history.
+    pub fn run(&mut self, line: &str) -> Result<UnitValue, CalculatorError> {
+        self.warnings.clear();
+        self.conversion = None;
+        self.conversion_zero = None;
+        self.binding = None;
+        self.uncertainty = None;
+        self.system = None;
+        self.sig_figs = None;
+        self.error_position = None;
+        let res = self.run_line(line);
+        match res {
+            Ok(val) => self.history.push((line.trim().to_owned(), val)),
+            // the position is only known for infix input
+            Err(CalculatorError::SyntaxError) if !self.rpn => self.error_position = with_stack(|| error_position(line)),
+            Err(_) => (),
+        }
+        res
+    }
+    /// Parse and evaluate a line of input
+    fn run_line(&mut self, line: &str) -> Result<UnitValue, CalculatorError> {
+        // each level of nesting in the line takes stack space
+        with_stack(|| self.parse_and_run(line))
+    }
+    fn parse_and_run(&mut self, line: &str) -> Result<UnitValue, CalculatorError> {
+        // postfix input has no bindings or conversions
+        if self.rpn {
+            return parse_rpn(line).and_then(|expr| self.run_expr(expr))
+        }
+        if let IResult::Done(_, (name, expr, keep_unit)) = binding(line.as_bytes()) {
+            // units and constants can't be shadowed
+            if get_unit(name.as_bytes()).is_some() || get_numerical_constant(name.as_bytes()).is_some() {
+                return Err(CalculatorError::SyntaxError)
+            }
+            let unit = if keep_unit { trailing_units(&expr) } else { None };
+            let val = try!(self.run_expr(expr));
+            self.variables.insert(name.clone(), val);
+            self.binding = Some(name.clone());
+            match unit {
+                Some(unit) => self.display_units.insert(name, unit),
+                None => self.display_units.remove(&name),
+            };
+            return Ok(val)
+        }
+        if let IResult::Done(_, ((expr, source), (target, text))) = conversion(line.as_bytes()) {
+            let scale = self.source_scale(&expr);
+            let target = try!(self.run_expr(target));
+            let mut val = try!(self.run_expr(expr));
+            try!(val.convert_to(&target));
+            // temperature scales have different zero points, so e.g. 0 degC is 273.15 K;
+            // the result is the absolute temperature
+            if let Some(to) = units::temperature_zero(text.trim()) {
+                match scale.and_then(|unit| units::temperature_zero(&unit)) {
+                    Some(from) => {
+                        val = try!(val.add(&UnitValue { value: from, unit: target.unit }));
+                        self.conversion_zero = Some(to);
+                    },
+                    // a temperature difference or a computed temperature has no zero point
+                    None if !to.is_zero() => return Err(CalculatorError::UnitError),
+                    None => {},
+                }
+            }
+            self.conversion = Some((text, target));
+            self.last_result = Some(val);
+            return Ok(val)
+        }
+        match raw_input(line.as_bytes()) {
+            IResult::Done(_, expr) => self.run_expr(expr),
+            _ => Err(CalculatorError::SyntaxError),
+        }
+    }
+    /// The unit a conversion's source is written in: the units of a single quantity such as
+    /// `100 degC` or `(-40 degF)`, or the unit a variable was assigned with
+    fn source_scale(&self, expr: &Expression) -> Option<String> {
+        match *expr {
+            Expression::Var(ref name) => self.display_units.get(name).cloned(),
+            Expression::Neg(ref a) => self.source_scale(a),
+            Expression::Mul(_, box Expression::Units(_, ref text)) => Some(text.clone()),
+            _ => None,
+        }
+    }
+    /// Evaluate a parsed line of input and remember the result
+    fn run_expr(&mut self, expr: Expression) -> Result<UnitValue, CalculatorError> {
+        self.system = unit_system(&expr);
+        let (res, sig_figs) = self.evaluate_precision(expr);
+        if self.track_sig_figs {
+            self.sig_figs = sig_figs.map(|s| s.figures);
+        }
+        match res {
+            Expression::Value(val) => {
+                self.check_degree(&val);
+                self.last_result = Some(val);
+                Ok(val)
+            },
+            // only the central value is kept as the result
+            Expression::Measured(m) => {
+                self.last_result = Some(m.value);
+                self.uncertainty = Some(m.uncertainty);
+                Ok(m.value)
+            },
+            Expression::Error(e) => Err(CalculatorError::from(e)),
+            // e.g. an undefined variable
+            _ => Err(CalculatorError::SyntaxError),
+        }
+    }
+    /// Write the history as CSV, with columns for the input, the result (as a decimal),
+    /// its unit, and whether it is exact
+    pub fn export_csv<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        try!(writeln!(output, "input,value,unit,exact"));
+        for &(ref line, ref val) in &self.history {
+            try!(writeln!(output, "{},{},{},{}", csv_field(line), val.value.as_float(),
+                          csv_field(&format!("{}", val.unit)), val.value.get_exact().is_some()));
+        }
+        Ok(())
+    }
+    /// Format the result of a line for display. The result of a unit conversion is shown in the
+    /// target unit. If the line is a variable bound with `:=` (or the binding itself), the result
+    /// is shown in that variable's display unit.
+    pub fn format(&self, line: &str, val: &UnitValue) -> String {
+        if let Some(ref unc) = self.uncertainty {
+            return format!("{} ± {}", val.format_with(self.display, self.precision), unc.format_with(self.display, self.precision))
+        }
+        if let Some(figures) = self.sig_figs {
+            let rounded = sigfig::round(val.value.as_float(), figures, self.precision.rounding);
+            return if val.unitless() { rounded } else { format!("{} {}", rounded, val.unit) }
+        }
+        if let Some((ref text, ref target)) = self.conversion {
+            let val = match self.conversion_zero {
+                Some(zero) => val.sub(&UnitValue { value: zero, unit: target.unit }),
+                None => Ok(*val),
+            };
+            if let Ok(n) = val.and_then(|val| val.convert_to(target)) {
+                return format!("{} {}", n.format_with(self.display, self.precision), text)
+            }
+        }
+        let name = match shown_var(line.as_bytes()) {
+            IResult::Done(_, name) => Some(name),
+            _ => None,
+        };
+        name.and_then(|name| self.display_units.get(&name))
+            .and_then(|unit| val.display_in(unit, self.display, self.precision))
+            .or_else(|| self.system.and_then(|system| units::simplify_units(&val.unit, system))
+                     .and_then(|(text, unit)| val.convert_to(&unit).ok()
+                               .map(|n| format!("{} {}", n.format_with(self.display, self.precision), text))))
+            .unwrap_or_else(|| val.format_with(self.display, self.precision))
+    }
+    /// Warn if a result's unit has a suspiciously high degree
+    fn check_degree(&mut self, val: &UnitValue) {
+        if let Some(max) = self.max_degree {
+            let degree = val.unit.degree();
+            if degree > max as f64 {
+                self.warn(&format!("the unit {} has degree {}, which is unusual", val.unit, degree));
+            }
+        }
+    }
+    /// Add a warning for the current line (unless it was already given)
+    fn warn(&mut self, msg: &str) {
+        if !self.warnings.iter().any(|w| w == msg) {
+            self.warnings.push(msg.to_owned());
+        }
+    }
+    /// Evaluate an expression (bottom-up) as far as possible
+    pub fn evaluate(&mut self, expr: Expression) -> Expression {
+        self.evaluate_precision(expr).0
+    }
+    /// Evaluate an expression, along with the precision of its value (None if it has no
+    /// inexact numbers, or it was found with an operation other than arithmetic)
+    fn evaluate_precision(&mut self, expr: Expression) -> (Expression, Option<SigFigs>) {
+        use Expression as E;
+        // A chain of operators (e.g. 1 + 2 + ... + 1000, or 3!!!) is nested on the left, so the
+        // left operands are followed with a loop rather than recursion; otherwise long input
+        // would overflow the stack.
+        let mut chain = Vec::new();
+        let mut left = expr;
+        let expr = loop {
+            left = match split_binary(left) {
+                Ok((op, a, b)) => {
+                    chain.push(Operator::Binary(op, b));
+                    *a
+                },
+                Err(expr) => match split_postfix(expr) {
+                    Ok((op, a)) => {
+                        chain.push(Operator::Postfix(op));
+                        a
+                    },
+                    Err(expr) => break expr,
+                },
+            }
+        };
+        // whether the expression so far was written with only integers
+        let mut integral = expr.is_integral();
+        let (res, mut sig_figs) = match expr {
+            E::Number(literal) => {
+                let sig_figs = SigFigs::of_literal(&literal);
+                (E::Number(literal), Some(sig_figs))
+            },
+            E::Neg(a) => {
+                let (a, sig_figs) = self.evaluate_precision(*a);
+                (E::Neg(Box::new(a)), sig_figs)
+            },
+            // other operations (e.g. functions) are treated as exact
+            E::Call(f, args) => {
+                let radians = self.in_radians(&f, &args);
+                let args = args.into_iter().map(|a| self.evaluate(a)).collect();
+                (if radians { self.call_in_radians(f, args) } else { E::Call(f, args) }, None)
+            },
+            expr => (expr, None)
+        };
+        let mut res = self.evaluate1(res, false);
+        while let Some(op) = chain.pop() {
+            match op {
+                Operator::Binary(op, b) => {
+                    integral = integral && b.is_integral();
+                    let (b, b_sig_figs) = self.evaluate_precision(*b);
+                    let expr = op(Box::new(res), Box::new(b));
+                    let rule = precision_rule(&expr);
+                    res = self.evaluate1(expr, integral);
+                    sig_figs = match (rule, &res) {
+                        (Some(rule), &E::Value(ref v)) => rule(sig_figs, b_sig_figs, v.value.as_float()),
+                        _ => None,
+                    };
+                },
+                Operator::Postfix(op) => {
+                    res = self.evaluate1(op(res), integral);
+                    sig_figs = None;
+                },
+            }
+        }
+        (res, sig_figs)
+    }
+    /// Evaluate an expression whose operands have been evaluated. `integral` is whether it was
+    /// written with only integers (for integer division).
+    fn evaluate1(&mut self, expr: Expression, integral: bool) -> Expression {
+        use Expression as E;
+        // integer division applies only to numbers written as integers
+        if self.mode == CalcMode::Integer && integral {
+            if let E::Div(box E::Value(ref a), box E::Value(ref b)) = expr {
+                if let Some(q) = integer_divide(a, b) {
+                    let res = make_value(q);
+                    self.record_step(step_text(&expr), &res);
+                    return res
+                }
+            }
+        }
+        // whether this is a multiplication of exact values (e.g. an implied multiplication chain)
+        let exact_mul = match expr {
+            E::Mul(box E::Value(ref a), box E::Value(ref b)) => a.value.get_exact().is_some() && b.value.get_exact().is_some(),
+            _ => false,
+        };
+        // the operation being done, if steps are recorded and the operands are known
+        let step = if self.steps.is_some() { step_text(&expr) } else { None };
+        let res = match (self.simplify1(expr), self.max_denominator) {
+            // don't carry around fractions with large denominators
+            (E::Value(a), Some(max)) => E::Value(UnitValue { value: a.value.limit_denominator(max), unit: a.unit }),
+            (expr, _) => expr
+        };
+        // without unit checking, every value (including units themselves) is unitless
+        let res = match res {
+            E::Value(a) if !self.strict_units => E::Value(UnitValue { value: a.value, unit: Unit::zero() }),
+            res => res
+        };
+        // the result silently became inexact
+        if let E::Value(ref a) = res {
+            if exact_mul && a.inexact_reason() == Some(InexactReason::Overflow) {
+                self.warn("a multiplication overflowed, so the result is inexact");
+            }
+        }
+        self.record_step(step, &res);
+        res
+    }
+    /// Record an operation and its result (if steps are being recorded)
+    fn record_step(&mut self, step: Option<String>, res: &Expression) {
+        if let (Some(step), Some(steps)) = (step, self.steps.as_mut()) {
+            steps.push(format!("{} = {}", step, res));
+        }
+    }
+    /// Whether a call (before its arguments are evaluated) is a trigonometric function of an
+    /// angle that is already in radians, even in degree mode: one written with an angle unit
+    /// (e.g. `sin(90 deg)` or `cos(pi/2 rad)`) or converted with `deg()` (e.g. `sin(deg(30))`).
+    /// An angle stored in a variable has lost its unit, so `sin(x)` is always in degrees.
+    fn in_radians(&self, name: &str, args: &[Expression]) -> bool {
+        self.angles == AngleMode::Degrees && (name == "sin" || name == "cos" || name == "tan")
+            && args.iter().any(written_in_radians)
+    }
+    /// Call a builtin function of known values without converting its angle from degrees (the
+    /// call is left as it is otherwise)
+    fn call_in_radians(&self, name: String, args: Vec<Expression>) -> Expression {
+        if !self.functions.contains_key(&name) && args.iter().all(Expression::is_known) {
+            if let Some((arity, f)) = get_function(name.as_bytes()) {
+                if arity_matches(arity, args.len()) {
+                    return make_value(f(args.iter().map(Expression::extract_value).collect()))
+                }
+            }
+        }
+        self.simplify1(Expression::Call(name, args))
+    }
+    /// Call a builtin function; in degree mode, angles are converted to and from radians, and
+    /// in radian mode, an angle written in degrees and minutes (e.g. `40d30m`) is in radians
+    fn call_builtin(&self, name: &str, f: &Function, mut args: Vec<UnitValue>) -> Result<UnitValue, ArithmeticError> {
+        // halfway cases depend on the rounding mode (rounding depends on the unit, so the value
+        // must be unitless)
+        if name == "round" {
+            return if args[0].unitless() {
+                Ok(UnitValue::with_unit(args[0].value.round(self.precision.rounding), args[0].unit))
+            } else {
+                Err(ArithmeticError::UnitError)
+            }
+        }
+        let degree = units::get("deg").expect("deg is a unit");
+        if self.angles == AngleMode::Radians {
+            return if name == "dms" { f(args).and_then(|a| a.mul(&degree)) } else { f(args) }
+        }
+        match name {
+            "sin" | "cos" | "tan" => {
+                args[0] = try!(args[0].mul(&degree));
+                f(args)
+            },
+            "asin" | "acos" | "atan" | "atan2" => f(args).and_then(|a| a.div(&degree)),
+            _ => f(args),
+        }
+    }
+    /// Simplify 1 part of an expression
+    fn simplify1(&self, expr: Expression) -> Expression {
+        /// All values in an array are known
+        fn all_known(a: &Vec<Expression>) -> bool {
+            a.iter().all(Expression::is_known)
+        }
+        /// Some value is an error, so we should return an error
+        fn any_error(a: &Vec<Expression>) -> bool {
+            a.iter().any(Expression::is_error)
+        }
+        /// Make it more readable by renaming types
+        use Expression as E;
+        use Expression::Value as V;
+        if let Some(res) = simplify_measured(&expr) {
+            return res
+        }
+        if let Some(res) = cancel_factors(&expr) {
+            return res
+        }
+        match expr {
+            // a decimal is exact if it fits in a Rational, and otherwise a float (never made
+            // exact again, since the float is not what was typed)
+            E::Number(ref a) => match Value::from_decimal(a) {
+                Ok(v) => V(UnitValue::with_unit(v, Unit::zero())),
+                Err(ArithmeticError::OverflowError) => match a.parse() {
+                    Ok(f) => make_value(Value::inexact(f, InexactReason::FloatInput).map(|v| UnitValue::with_unit(v, Unit::zero()))),
+                    Err(_) => E::Error(ArithmeticError::DomainError),
+                },
+                Err(e) => E::Error(e),
+            },
+            E::Units(a, _) => V(a),
+            // undefined variables are left unknown
+            E::Var(a) => match self.variables.get(&a) {
+                Some(&val) => V(val),
+                None => E::Var(a),
+            },
+            E::Exp(box V(ref a), box V(ref b)) => make_value(a.pow(b)),
+            E::Exp(_, box e @ E::Error(_)) => e,
+            E::Exp(box e @ E::Error(_), _) => e,
+            E::Mul(box V(ref a), box V(ref b)) => make_value(a.mul(b)),
+            E::Mul(_, box e @ E::Error(_)) => e,
+            E::Mul(box e @ E::Error(_), _) => e,
+            E::Div(box V(ref a), box V(ref b)) => make_value(a.div(b)),
+            E::Div(_, box e @ E::Error(_)) => e,
+            E::Div(box e @ E::Error(_), _) => e,
+            E::Add(box V(ref a), box V(ref b)) => make_value(a.add(b)),
+            E::Add(_, box e @ E::Error(_)) => e,
+            E::Add(box e @ E::Error(_), _) => e,
+            E::Sub(box V(ref a), box V(ref b)) => make_value(a.sub(b)),
+            E::Sub(_, box e @ E::Error(_)) => e,
+            E::Sub(box e @ E::Error(_), _) => e,
+            E::Neg(box V(a)) => make_value(-a),
+            E::Neg(box E::Neg(box a)) => a,
+            E::Neg(box e @ E::Error(_)) => e,
+            E::PlusMinus(box V(a), box V(b)) => measured(Measurement::new(a, b)),
+            E::PlusMinus(_, box e @ E::Error(_)) => e,
+            E::PlusMinus(box e @ E::Error(_), _) => e,
+            E::Fact(box V(ref a)) => make_value(a.factorial()),
+            E::Fact(box e @ E::Error(_)) => e,
+            /// Call a function with the values of the arguments
+            E::Call(name, a) if all_known(&a) => {
+                let args: Vec<UnitValue> = a.iter().map(Expression::extract_value).collect();
+                // registered functions come first; a function given the wrong number of
+                // arguments is left unknown
+                if let Some(&(arity, ref f)) = self.functions.get(&name) {
+                    return if arity_matches(arity, args.len()) { make_value(f(args)) } else { E::Call(name, a) }
+                }
+                if let Some((arity, f)) = get_function(name.as_bytes()) {
+                    return if arity_matches(arity, args.len()) { make_value(self.call_builtin(&name, &f, args)) } else { E::Call(name, a) }
+                }
+                match self.variables.get(&name) {
+                    // a variable followed by parentheses is a multiplication, like a unit
+                    Some(val) if args.len() == 1 => make_value(val.mul(&args[0])),
+                    // undefined functions are left unknown
+                    _ => E::Call(name, a),
+                }
+            },
+            /// Forward the first error
+            E::Call(_, ref a) if any_error(a) => match a.iter().find(|e| e.is_error()).expect("no error found") {
+                &E::Error(a) => E::Error(a),
+                _ => panic!("not actually an error")
+            },
+            expr => expr
+        }
+    }
+}
+
+/// Make a measurement Expression from a result
+fn measured(res: Result<Measurement, ArithmeticError>) -> Expression {
+    res.map(|m| Expression::Measured(Box::new(m))).unwrap_or_else(Expression::Error)
+}
+
+/// Simplify arithmetic where an operand has an uncertainty (other values have none).
+/// Returns None for anything else.
+fn simplify_measured(expr: &Expression) -> Option<Expression> {
+    use Expression as E;
+    /// A known value as a measurement
+    fn measurement(e: &Expression) -> Option<Measurement> {
+        match e {
+            &E::Value(a) => Some(Measurement::exact(a)),
+            &E::Measured(ref a) => Some(**a),
+            _ => None,
+        }
+    }
+    let (op, a, b): (fn(&Measurement, &Measurement) -> Result<Measurement, ArithmeticError>, _, _) = match expr {
+        &E::Neg(box E::Measured(ref a)) => return Some(measured(Ok(Measurement { value: -a.value, ..**a }))),
+        &E::Add(ref a, ref b) => (Measurement::add, a, b),
+        &E::Sub(ref a, ref b) => (Measurement::sub, a, b),
+        &E::Mul(ref a, ref b) => (Measurement::mul, a, b),
+        &E::Div(ref a, ref b) => (Measurement::div, a, b),
+        _ => return None,
+    };
+    match (&**a, &**b) {
+        (&E::Measured(_), _) | (_, &E::Measured(_)) => (),
+        _ => return None,
+    }
+    match (measurement(a), measurement(b)) {
+        (Some(a), Some(b)) => Some(measured(op(&a, &b))),
+        _ => None,
+    }
+}
+
+/// Cancel common factors in a fraction that is only partially known: a constant factor is
+/// reduced (`(2 x)/4` is `x/2`) and a shared factor is removed (`(x y)/x` is `y`). This is not
+/// full algebra; the fraction must be a product over one of its factors. Cancelling `x` assumes
+/// that it is not zero (and it is the same expression on both sides, so its units cancel too).
+/// Returns None for anything else.
+fn cancel_factors(expr: &Expression) -> Option<Expression> {
+    use Expression as E;
+    use Expression::Value as V;
+    /// An expression that is not (yet) known
+    fn unknown(e: &Expression) -> bool {
+        match e {
+            &V(_) | &E::Measured(_) | &E::Error(_) => false,
+            _ => true,
+        }
+    }
+    /// An exact unitless integer
+    fn integer(i: i64) -> Box<Expression> {
+        Box::new(V(UnitValue { value: Value::Exact(Rational { num: i, den: 1 }), unit: Unit::zero() }))
+    }
+    let (num, den) = match expr {
+        &E::Div(box E::Mul(ref a, ref b), ref den) => ((&**a, &**b), &**den),
+        _ => return None,
+    };
+    match (num, den) {
+        // a shared factor
+        ((a, b), c) | ((b, a), c) if unknown(c) && a == c => Some(b.clone()),
+        // a constant factor
+        ((&V(ref a), x), &V(ref b)) | ((x, &V(ref a)), &V(ref b)) if unknown(x) && !a.is_zero() => {
+            let factor = match a.div(b) {
+                Ok(factor) => factor,
+                // e.g. division by zero, which is left for evaluation
+                Err(_) => return None,
+            };
+            let x = Box::new(x.clone());
+            match factor.value.get_exact() {
+                Some(r) if factor.unit == Unit::zero() => {
+                    let num = match r.num {
+                        1 => *x,
+                        -1 => E::Neg(x),
+                        n => E::Mul(integer(n), x),
+                    };
+                    Some(if r.den == 1 { num } else { E::Div(Box::new(num), integer(r.den as i64)) })
+                },
+                // inexact or with units, so kept as a coefficient
+                _ => Some(E::Mul(Box::new(V(factor)), x)),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Whether an expression was written with an angle unit (`deg` or `rad`) or converted to
+/// radians with `deg()`, so its value is in radians
+fn written_in_radians(expr: &Expression) -> bool {
+    use Expression as E;
+    match expr {
+        &E::Value(ref v) | &E::Units(ref v, _) => Some(*v) == units::get("deg") || Some(*v) == units::get("rad"),
+        &E::Call(ref name, _) => name == "deg",
+        &E::Mul(ref a, ref b) | &E::Div(ref a, ref b) | &E::Add(ref a, ref b) | &E::Sub(ref a, ref b)
+            => written_in_radians(a) || written_in_radians(b),
+        &E::Neg(ref a) => written_in_radians(a),
+        _ => false,
+    }
+}
+
+/// The unit system of the first units written in an expression, if it has any (variables
+/// don't remember the system they were written in)
+fn unit_system(expr: &Expression) -> Option<units::System> {
+    use Expression as E;
+    // the left operand is searched first; a stack is used rather than recursion, since long
+    // input can be nested deeply
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        match expr {
+            &E::Units(_, ref text) => return Some(units::written_system(text)),
+            &E::Exp(ref a, ref b) | &E::Mul(ref a, ref b) | &E::Div(ref a, ref b) | &E::Add(ref a, ref b)
+                | &E::Sub(ref a, ref b) | &E::PlusMinus(ref a, ref b) => {
+                stack.push(b);
+                stack.push(a);
+            },
+            &E::Neg(ref a) | &E::Fact(ref a) => stack.push(a),
+            &E::Call(_, ref args) => stack.extend(args.iter().rev()),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// A binary operator
+type BinaryOp = fn(Box<Expression>, Box<Expression>) -> Expression;
+
+/// A postfix operator (a factorial or percentage)
+type PostfixOp = fn(Expression) -> Expression;
+
+/// An operator whose left operand is evaluated before it
+enum Operator {
+    Binary(BinaryOp, Box<Expression>),
+    Postfix(PostfixOp),
+}
+
+/// Split a postfix operation (e.g. `5!` or `50%`) into the operator and its operand (or give
+/// back the expression if it is not one)
+fn split_postfix(expr: Expression) -> Result<(PostfixOp, Expression), Expression> {
+    use Expression as E;
+    match expr {
+        E::Fact(a) => Ok((|a| E::Fact(Box::new(a)), *a)),
+        E::Call(f, mut args) => if f == "percent" && args.len() == 1 {
+            Ok((|a| E::Call("percent".to_owned(), vec![a]), args.remove(0)))
+        } else {
+            Err(E::Call(f, args))
+        },
+        expr => Err(expr),
+    }
+}
+
+/// How the precision of an arithmetic operation's result is found from that of its operands
+/// (None for other operations, which are treated as exact)
+fn precision_rule(expr: &Expression) -> Option<fn(Option<SigFigs>, Option<SigFigs>, f64) -> Option<SigFigs>> {
+    use Expression as E;
+    match *expr {
+        E::Mul(..) | E::Div(..) => Some(sigfig::product),
+        E::Add(..) | E::Sub(..) => Some(sigfig::sum),
+        _ => None,
+    }
+}
+
+/// Split a binary operation into the operator and its operands (or give back the expression
+/// if it is not a binary operation)
+fn split_binary(expr: Expression) -> Result<(BinaryOp, Box<Expression>, Box<Expression>), Expression> {
+    use Expression as E;
+    match expr {
+        E::Exp(a, b) => Ok((E::Exp, a, b)),
+        E::Mul(a, b) => Ok((E::Mul, a, b)),
+        E::Div(a, b) => Ok((E::Div, a, b)),
+        E::Add(a, b) => Ok((E::Add, a, b)),
+        E::Sub(a, b) => Ok((E::Sub, a, b)),
+        E::PlusMinus(a, b) => Ok((E::PlusMinus, a, b)),
+        expr => Err(expr),
+    }
+}
+
+/// Describe an operation on known values (e.g. `3 * 4`); None for anything else
+fn step_text(expr: &Expression) -> Option<String> {
+    use Expression as E;
+    use Expression::Value as V;
+    match expr {
+        &E::Exp(box V(ref a), box V(ref b)) => Some(format!("{}^{}", a, b)),
+        &E::Mul(box V(ref a), box V(ref b)) => Some(format!("{} * {}", a, b)),
+        &E::Div(box V(ref a), box V(ref b)) => Some(format!("{} / {}", a, b)),
+        &E::Add(box V(ref a), box V(ref b)) => Some(format!("{} + {}", a, b)),
+        &E::Sub(box V(ref a), box V(ref b)) => Some(format!("{} - {}", a, b)),
+        &E::Fact(box V(ref a)) => Some(format!("{}!", a)),
+        _ => None,
+    }
+}
+
+/// The command a short form stands for (e.g. `display scientific 3` for `:sci 3`), or None if
+/// it is not one. The short forms change how results are displayed:
+/// `:frac`, `:dec`, `:mixed`, `:repeat` and `:sci [N]` for `display ...`, and `:precision [N]`
+/// for `precision [N]`.
+fn short_command(line: &str) -> Option<String> {
+    let mut words = line.splitn(2, char::is_whitespace);
+    let command = match words.next() {
+        Some(":frac") => "display fraction",
+        Some(":dec") => "display decimal",
+        Some(":mixed") => "display mixed",
+        Some(":repeat") => "display repeating",
+        Some(":sci") => "display scientific",
+        Some(":precision") => "precision",
+        _ => return None,
+    };
+    Some(format!("{} {}", command, words.next().unwrap_or("")))
+}
+
+/// Quote a CSV field if it contains a comma, quote, or newline
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// The units an expression ends in, as written (e.g. `W` in `500 W`), if it ends in units
+/// that give the unit of its value: those of its last term or factor
+fn trailing_units(expr: &Expression) -> Option<String> {
+    use Expression as E;
+    match *expr {
+        E::Units(_, ref text) => Some(text.trim().to_owned()),
+        E::Mul(_, ref b) | E::Add(_, ref b) | E::Sub(_, ref b) | E::PlusMinus(_, ref b) => trailing_units(b),
+        E::Neg(ref a) => trailing_units(a),
+        _ => None,
+    }
+}
+
+/// Truncating division of two unitless exact integers (None if they are not integers)
+fn integer_divide(a: &UnitValue, b: &UnitValue) -> Option<Result<UnitValue, ArithmeticError>> {
+    match (a.value.get_exact(), b.value.get_exact()) {
+        (Some(x), Some(y)) if x.is_integer() && y.is_integer() && a.unitless() && b.unitless() => {
+            if y.is_zero() {
+                return Some(Err(ArithmeticError::DivideByZeroError))
+            }
+            Some(x.div(y)
+                 .map(|q| UnitValue { value: Value::Exact(q.trunc()), unit: a.unit })
+                 .map_err(ArithmeticError::from))
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uval::UnitValue;
+    use rational::AsFloat;
+    use CalculatorError;
+    use std::f64::consts;
+
+    // evaluate a line that is expected to be valid
+    macro_rules! run {
+        ( $c:expr, $x:expr ) => ($c.run($x).unwrap())
+    }
+
+    #[test]
+    fn test_integer_mode() {
+        let mut calc = Calculator::new();
+        assert_eq!(run!(calc, "7/2"), UnitValue::from_input(3.5).unwrap());
+        assert_eq!(calc.command("calcmode integer"), Some(Ok(String::new())));
+        assert_eq!(calc.mode, CalcMode::Integer);
+        assert_eq!(run!(calc, "7/2"), UnitValue::from_input(3.0).unwrap());
+        assert_eq!(run!(calc, "-7/2"), UnitValue::from_input(-3.0).unwrap());
+        assert_eq!(run!(calc, "(1+6)/2*2"), UnitValue::from_input(6.0).unwrap());
+        assert_eq!(run!(calc, "8/2"), UnitValue::from_input(4.0).unwrap());
+        // a non-integer operand falls back to normal division
+        assert_eq!(run!(calc, "7.0/2"), UnitValue::from_input(3.5).unwrap());
+        assert_eq!(run!(calc, "7/2.0"), UnitValue::from_input(3.5).unwrap());
+        assert_eq!(calc.run("7/0"), Err(CalculatorError::DivideByZeroError));
+        assert_eq!(calc.command("calcmode normal"), Some(Ok(String::new())));
+        assert_eq!(run!(calc, "7/2"), UnitValue::from_input(3.5).unwrap());
+        assert!(calc.command("calcmode").unwrap().is_err());
+        assert_eq!(calc.command("7/2"), None);
+    }
+
+    #[test]
+    fn test_max_denominator() {
+        let mut calc = Calculator::new();
+        assert!(run!(calc, "1/3 + 1/7").value.get_exact().is_some());
+        assert_eq!(calc.command("maxden 10"), Some(Ok(String::new())));
+        assert_eq!(calc.max_denominator, Some(10));
+        let res = run!(calc, "1/3 + 1/7");
+        assert!(res.value.get_exact().is_none());
+        assert!((res.as_float() - 10.0 / 21.0).abs() < 1e-12);
+        assert!(run!(calc, "1/2 + 1/2").value.get_exact().is_some());
+        assert!(run!(calc, "3/10").value.get_exact().is_some());
+        assert!(run!(calc, "1/11").value.get_exact().is_none());
+        assert!(calc.command("maxden 0").unwrap().is_err());
+        assert!(calc.command("maxden x").unwrap().is_err());
+        assert_eq!(calc.command("maxden off"), Some(Ok(String::new())));
+        assert!(run!(calc, "1/3 + 1/7").value.get_exact().is_some());
+    }
+
+    #[test]
+    fn test_inexact_reason() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("why-inexact"), Some(Ok("no result yet".to_owned())));
+        assert_eq!(run!(calc, "sin(1)").inexact_reason(), Some(InexactReason::IrrationalFunction));
+        assert_eq!(run!(calc, "2 sin(1) + 1").inexact_reason(), Some(InexactReason::IrrationalFunction));
+        assert_eq!(run!(calc, "1/65536/65536/65536/65536").inexact_reason(), Some(InexactReason::Overflow));
+        assert_eq!(calc.command("why-inexact"), Some(Ok("an exact calculation overflowed".to_owned())));
+        assert_eq!(run!(calc, "0.12345678901234567891").inexact_reason(), Some(InexactReason::FloatInput));
+        assert_eq!(run!(calc, "pi").inexact_reason(), Some(InexactReason::ExplicitFloat));
+        assert_eq!(run!(calc, "2^(1/2)").inexact_reason(), Some(InexactReason::IrrationalFunction));
+        assert_eq!(run!(calc, "1/2").inexact_reason(), None);
+        assert_eq!(calc.command("why-inexact"), Some(Ok("the last result is exact".to_owned())));
+        calc.command("maxden 10");
+        assert_eq!(run!(calc, "1/11").inexact_reason(), Some(InexactReason::LargeDenominator));
+    }
+
+    #[test]
+    fn test_display_units() {
+        let mut calc = Calculator::new();
+        let power = run!(calc, "power := 1 kW");
+        assert_eq!(calc.format("power := 1 kW", &power), "1 kW");
+        assert_eq!(run!(calc, "power"), power);
+        assert_eq!(calc.format("power", &power), "1 kW");
+        // further calculations are in base units
+        let doubled = run!(calc, "2 power");
+        assert_eq!(calc.format("2 power", &doubled), format!("{}", doubled));
+        assert!(!calc.format("2 power", &doubled).contains("kW"));
+        // the display unit must have the same dimensions
+        let len = run!(calc, "len := 3 m / s");
+        assert_eq!(calc.format("len", &len), "3 m/s");
+        // rebinding without a unit forgets the display unit
+        let power = run!(calc, "power := 5");
+        assert_eq!(calc.format("power", &power), "5");
+        // the unit is that of the last term, and only `:=` remembers it
+        let power = run!(calc, "power:=1 kW + 500 W");
+        assert_eq!(calc.format("power:=1 kW + 500 W", &power), "1500 W");
+        assert_eq!(calc.format(" power ", &power), "1500 W");
+        let power = run!(calc, "power := 2 kW * 3");
+        assert!(!calc.format("power", &power).contains("kW"));
+        let power = run!(calc, "power = 1 kW");
+        assert!(!calc.format("power", &power).contains("kW"));
+        let ratio = run!(calc, "ratio := 1 kW / W");
+        assert_eq!(calc.format("ratio", &ratio), "1000");
+        assert_eq!(calc.run("m := 5"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc.run("undefined"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc.error_position, None);
+        assert_eq!(calc.run("1 + * 2"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc.error_position, Some(4));
+        run!(calc, "1 + 2");
+        assert_eq!(calc.error_position, None);
+        assert_eq!(calc.run("x := 2?"), Err(CalculatorError::SyntaxError));
+    }
+
+    #[test]
+    fn test_factor_command() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("factor 360"), Some(Ok("2^3 * 3^2 * 5".to_owned())));
+        assert_eq!(calc.command("factor 97"), Some(Ok("97".to_owned())));
+        assert_eq!(calc.command("factor 2^10 * 3"), Some(Ok("2^10 * 3".to_owned())));
+        assert_eq!(calc.command("factor 1"), Some(Ok("1".to_owned())));
+        assert!(calc.command("factor 1/2").unwrap().is_err());
+        assert!(calc.command("factor 2 m").unwrap().is_err());
+        assert_eq!(calc.command("factor(360)"), None);
+    }
+
+    #[test]
+    fn test_register_function() {
+        let mut calc = Calculator::new();
+        calc.register_function("double", 1, |a| a[0].add(&a[0]));
+        assert_eq!(run!(calc, "double(21)"), UnitValue::from_input(42.0).unwrap());
+        assert_eq!(run!(calc, "double(2 m) / m"), UnitValue::from_input(4.0).unwrap());
+        assert_eq!(calc.run("double(1, 2)"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc.run("double(1 m + 1 s)"), Err(CalculatorError::UnitError));
+        // registered functions replace builtins
+        calc.register_function("abs", 1, |_| UnitValue::from_input(7.0));
+        assert_eq!(run!(calc, "abs(-1)"), UnitValue::from_input(7.0).unwrap());
+        assert_eq!(run!(Calculator::new(), "abs(-1)"), UnitValue::from_input(1.0).unwrap());
+        // a name that is not a function
+        assert_eq!(calc.run("triple(2)"), Err(CalculatorError::SyntaxError));
+        run!(calc, "x := 5");
+        assert_eq!(run!(calc, "x(2)"), UnitValue::from_input(10.0).unwrap());
+    }
+
+    #[test]
+    fn test_steps_command() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("steps 2 + 3 * 4"), Some(Ok("3 * 4 = 12\n2 + 12 = 14".to_owned())));
+        assert_eq!(calc.command("steps (1 + 2)^2 / 6"), Some(Ok("1 + 2 = 3\n3^2 = 9\n9 / 6 = 3/2".to_owned())));
+        assert_eq!(calc.command("steps 2 km + 3 m"), Some(Ok("2 * 1000 m = 2000 m\n3 * 1 m = 3 m\n2000 m + 3 m = 2003 m".to_owned())));
+        assert_eq!(calc.command("steps 5"), Some(Ok("5".to_owned())));
+        assert_eq!(calc.command("steps 1 + 1/0"), Some(Err("1 / 0 = DivideByZeroError\ndivision by zero".to_owned())));
+        calc.mode = CalcMode::Integer;
+        assert_eq!(calc.command("steps 7/2 + 1"), Some(Ok("7 / 2 = 3\n3 + 1 = 4".to_owned())));
+        // steps are only recorded for the command
+        assert!(calc.run("1 + 1").is_ok());
+        assert_eq!(calc.steps, None);
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let mut calc = Calculator::new();
+        run!(calc, "2 m + 3 m");
+        run!(calc, "atan2(1, 1)");
+        assert!(calc.run("1 +").is_err());
+        run!(calc, "1/4");
+        let mut output = Vec::new();
+        calc.export_csv(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(),
+                   format!("input,value,unit,exact\n2 m + 3 m,5,m,true\n\"atan2(1, 1)\",{},,false\n1/4,0.25,,true\n",
+                           1f64.atan2(1.0)));
+    }
+
+    #[test]
+    fn test_overflow_warning() {
+        let mut calc = Calculator::new();
+        assert!(run!(calc, "1(2)3(4)5(6)7(8)9(10)11(12)13(14)").value.get_exact().is_some());
+        assert!(calc.warnings.is_empty());
+        let res = run!(calc, "1(2)3(4)5(6)7(8)9(10)11(12)13(14)15(16)17(18)19(20)21(22)");
+        assert_eq!(res.inexact_reason(), Some(InexactReason::Overflow));
+        assert_eq!(calc.warnings, vec!["a multiplication overflowed, so the result is inexact".to_owned()]);
+        // inexact operands don't cause a warning
+        run!(calc, "pi(3)");
+        assert!(calc.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_conversion() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "5 km in m"), "5000 m");
+        assert_eq!(show(&mut calc, "1 mi to km"), "25146/15625 km");
+        assert_eq!(show(&mut calc, "100 km/hr in m/s"), "250/9 m/s");
+        assert_eq!(show(&mut calc, "2 ft in in"), "24 in");
+        // without a conversion, customary units are shown in feet
+        assert_eq!(show(&mut calc, "5 in"), "5/12 ft");
+        // the value itself is still in base units
+        assert_eq!(run!(calc, "5 km in mi"), run!(calc, "5000 m"));
+        assert_eq!(calc.run("5 km in s"), Err(CalculatorError::UnitError));
+        assert_eq!(calc.run("5 km to"), Err(CalculatorError::SyntaxError));
+    }
+
+    #[test]
+    fn test_time_units() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "2 h in min"), "120 min");
+        assert_eq!(show(&mut calc, "90 min in h"), "3/2 h");
+        assert_eq!(show(&mut calc, "1 day in h"), "24 h");
+        assert_eq!(show(&mut calc, "36 h in day"), "3/2 day");
+        assert_eq!(show(&mut calc, "1 day in min"), "1440 min");
+        assert_eq!(show(&mut calc, "1 h in s"), "3600 s");
+        assert_eq!(run!(calc, "1 h"), run!(calc, "1 hr"));
+        // min is minutes, not milli-inches, but other prefixes still apply
+        assert_eq!(show(&mut calc, "1 min"), "60 s");
+        assert_eq!(show(&mut calc, "1 ms in s"), "1/1000 s");
+    }
+
+    #[test]
+    fn test_data_units() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "1 MiB in KiB"), "1024 KiB");
+        assert_eq!(show(&mut calc, "1 GiB in B"), "1073741824 B");
+        assert_eq!(show(&mut calc, "1 TiB in GiB"), "1024 GiB");
+        assert_eq!(show(&mut calc, "1 KiB"), "1024 B");
+        assert_eq!(show(&mut calc, "16 bit in byte"), "2 byte");
+        assert_eq!(show(&mut calc, "1 GiB in bytes"), "1073741824 bytes");
+        assert_eq!(show(&mut calc, "1 TiB in bits"), "8796093022208 bits");
+        assert!(run!(calc, "1 TiB").value.get_exact().is_some());
+        // SI prefixes are decimal
+        assert_eq!(show(&mut calc, "1 MB in kB"), "1000 kB");
+        assert_eq!(show(&mut calc, "1 GB in B"), "1000000000 B");
+        assert_eq!(show(&mut calc, "1 KiB in kB"), "128/125 kB");
+        assert!(run!(calc, "1 kB") != run!(calc, "1 KiB"));
+        assert_eq!(show(&mut calc, "2 MB/s * 1 min in MiB"), "234375/2048 MiB");
+        assert_eq!(calc.run("1 B + 1"), Err(CalculatorError::UnitError));
+    }
+
+    #[test]
+    fn test_temperature_conversion() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        calc.display = DisplayMode::Decimal;
+        assert_eq!(show(&mut calc, "100 degC to degF"), "212 degF");
+        assert_eq!(show(&mut calc, "212 degF to degC"), "100 degC");
+        assert_eq!(show(&mut calc, "0 degC to K"), "273.15 K");
+        assert_eq!(show(&mut calc, "300 K in degC"), "26.85 degC");
+        assert_eq!(show(&mut calc, "32 degF to K"), "273.15 K");
+        assert_eq!(show(&mut calc, "0 K to degF"), "-459.67 degF");
+        assert_eq!(show(&mut calc, "-40 degC to degF"), "-40 degF");
+        // the result is the absolute temperature
+        assert_eq!(run!(calc, "0 degC to K"), run!(calc, "273.15 K"));
+        // as units, degC and degF are temperature differences
+        assert_eq!(show(&mut calc, "20 degC + 20 degC"), "40 K");
+        assert_eq!(show(&mut calc, "(100 degC) to degF"), "212 degF");
+        assert_eq!(show(&mut calc, "(-40 degC) to degF"), "-40 degF");
+        calc.run("x := 100 degC").unwrap();
+        assert_eq!(show(&mut calc, "x to degF"), "212 degF");
+        // a sum or a product has no zero point to convert from
+        assert_eq!(calc.run("(20 degC + 20 degC) to degF"), Err(CalculatorError::UnitError));
+        assert_eq!(calc.run("2 * x to degC"), Err(CalculatorError::UnitError));
+        assert_eq!(show(&mut calc, "20 degC + 20 degC to K"), "40 K");
+        assert_eq!(show(&mut calc, "50 degF to degC"), "10 degC");
+        assert_eq!(calc.run("100 degC to m"), Err(CalculatorError::UnitError));
+    }
+
+    #[test]
+    fn test_uncertainty() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "10 ± 0.5"), "10 ± 1/2");
+        assert_eq!(show(&mut calc, "(10 ± 0.5) * 2"), "20 ± 1");
+        assert_eq!(show(&mut calc, "(10 ± 0.5) + (2 ± 0.25)"), "12 ± 3/4");
+        assert_eq!(show(&mut calc, "(10 ± 0.5) - (2 ± 0.25)"), "8 ± 3/4");
+        assert_eq!(show(&mut calc, "(10 ± 0.5) * (2 ± 0.25)"), "20 ± 7/2");
+        assert_eq!(show(&mut calc, "1 / (2 ± 0.5)"), "1/2 ± 1/8");
+        assert_eq!(show(&mut calc, "-(10 ± 0.5)"), "-10 ± 1/2");
+        // ± has the same precedence as + and -
+        assert_eq!(show(&mut calc, "1 + 2 ± 1/2 + 3"), "6 ± 1/2");
+        // units apply to both parts
+        assert_eq!(show(&mut calc, "(2 m ± 1 cm) * 3"), "6 m ± 3/100 m");
+        assert_eq!(calc.run("2 m ± 1 s"), Err(CalculatorError::UnitError));
+        assert_eq!(calc.run("(1 ± 1) / 0"), Err(CalculatorError::DivideByZeroError));
+        // the result is the central value
+        assert_eq!(run!(calc, "10 ± 0.5"), UnitValue::from_input(10.0).unwrap());
+        assert_eq!(show(&mut calc, "1 + 1"), "2");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("normalize"), Some(Ok("no result yet".to_owned())));
+        run!(calc, "6/4 m m");
+        assert_eq!(calc.command("normalize"), Some(Ok("3/2 m^2".to_owned())));
+        // conversions, display units, and the display mode are ignored
+        run!(calc, "d := 3 mi");
+        assert_eq!(calc.command("normalize"), Some(Ok("603504/125 m".to_owned())));
+        run!(calc, "1 mi in km");
+        assert_eq!(calc.command("normalize"), Some(Ok("201168/125 m".to_owned())));
+        calc.display = DisplayMode::Decimal;
+        run!(calc, "s * 4 kg / 6");
+        assert_eq!(calc.command("normalize"), Some(Ok("2/3 kg*s".to_owned())));
+    }
+
+    #[test]
+    fn test_display_mode() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "1/3"), "1/3");
+        assert_eq!(calc.command("display repeating"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3"), "0.(3)");
+        assert_eq!(show(&mut calc, "1/6"), "0.1(6)");
+        assert_eq!(show(&mut calc, "7/2 m"), "3.5 m");
+        assert_eq!(show(&mut calc, "1 mi to km"), "1.609344 km");
+        assert_eq!(show(&mut calc, "1/97"), "0.01030927835051546391...");
+        assert_eq!(calc.command("display decimal"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3"), "0.3333333333333333");
+        assert_eq!(calc.command("display scientific"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1500 m/s"), "1.5e3 m/s");
+        assert_eq!(show(&mut calc, "1/3 m"), "1/3 m");
+        assert_eq!(show(&mut calc, "1 mi to km"), "1.609344e0 km");
+        assert_eq!(show(&mut calc, "sqrt(2e6) s"), "1.414213562373095e3 s");
+        assert_eq!(calc.command("display fraction"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3"), "1/3");
+        assert!(calc.command("display").unwrap().is_err());
+        assert_eq!(calc.command("display mixed"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "7/2"), "3 1/2");
+        assert_eq!(calc.command("display scientific 3"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3 * 1.0"), "3.33e-1");
+        assert!(calc.command("display scientific 0").unwrap().is_err());
+        assert_eq!(calc.command("precision 2"), Some(Ok(String::new())));
+        assert_eq!(calc.command("display decimal"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3"), "0.33");
+        assert_eq!(calc.command("precision"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "1/3"), "0.3333333333333333");
+        assert!(calc.command("precision two").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_short_commands() {
+        // each is the same as the command it stands for
+        let mut calc = Calculator::new();
+        for &(short, display) in &[(":dec", DisplayMode::Decimal), (":mixed", DisplayMode::Mixed), (":repeat", DisplayMode::Repeating),
+                                   (":sci", DisplayMode::Scientific), (":frac", DisplayMode::Fraction)] {
+            assert_eq!(calc.command(short), Some(Ok(String::new())));
+            assert_eq!(calc.display, display);
+        }
+        assert_eq!(calc.command(":sci 3"), Some(Ok(String::new())));
+        assert_eq!(calc.precision.scientific, Some(3));
+        assert_eq!(calc.command(":sci"), Some(Ok(String::new())));
+        assert_eq!(calc.precision.scientific, None);
+        assert_eq!(calc.command(":precision 4"), Some(Ok(String::new())));
+        assert_eq!(calc.precision.decimals, Some(4));
+        assert!(calc.command(":sci 0").unwrap().is_err());
+        assert!(calc.command(":dec 2").unwrap().is_err());
+        assert_eq!(calc.command(":fraction"), None);
+        // so they can be used in the config file too
+        assert!(calc.load_config(&b":dec\n:precision 2\n"[..]).unwrap().is_empty());
+        assert_eq!((calc.display, calc.precision.decimals), (DisplayMode::Decimal, Some(2)));
+    }
+
+    #[test]
+    fn test_sig_figs() {
+        let mut calc = Calculator::new();
+        /// Run a line and format its result
+        fn show(calc: &mut Calculator, line: &str) -> String {
+            let res = calc.run(line).unwrap();
+            calc.format(line, &res)
+        }
+        assert_eq!(show(&mut calc, "2.5 * 3.00"), "15/2");
+        assert_eq!(calc.command("sigfigs on"), Some(Ok(String::new())));
+        // multiplication and division keep the fewest significant figures
+        assert_eq!(show(&mut calc, "2.5 * 3.00"), "7.5");
+        assert_eq!(show(&mut calc, "2.50 * 3.00"), "7.50");
+        assert_eq!(show(&mut calc, "10.0 / 3.00"), "3.33");
+        assert_eq!(show(&mut calc, "10.0 / 3"), "3");
+        assert_eq!(show(&mut calc, "2.0 m * 3.14159"), "6.3 m");
+        // addition and subtraction keep the fewest decimal places
+        assert_eq!(show(&mut calc, "12.11 + 0.3"), "12.4");
+        assert_eq!(show(&mut calc, "100.5 - 99.5"), "1.0");
+        assert_eq!(show(&mut calc, "-(1.25 + 2.00)"), "-3.25");
+        assert_eq!(show(&mut calc, "-(1.25 + 2)"), "-3");
+        assert_eq!(show(&mut calc, "(1.2 + 3.45) * 2.000"), "9.3");
+        // units and constants don't limit the precision
+        assert_eq!(show(&mut calc, "1/3"), "0.3");
+        assert_eq!(show(&mut calc, "pi * 2.0"), "6.3");
+        assert_eq!(show(&mut calc, "km / m"), "1000");
+        assert_eq!(calc.sig_figs, None);
+        assert_eq!(show(&mut calc, "2.00 km"), "2000 m");
+        assert_eq!(calc.sig_figs, Some(3));
+        // the precision is found while evaluating, so each line is evaluated once
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        calc.register_function("count", 1, move |a| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(a[0])
+        });
+        assert_eq!(show(&mut calc, "count(2.5) * 3.00"), "7.50");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(calc.command("sigfigs off"), Some(Ok(String::new())));
+        assert_eq!(show(&mut calc, "2.5 * 3.00"), "15/2");
+        assert!(calc.command("sigfigs").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_unit_checking() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.run("1 m + 1 s"), Err(CalculatorError::UnitError));
+        assert_eq!(calc.command("units off"), Some(Ok(String::new())));
+        assert_eq!(run!(calc, "1 m + 1 s"), UnitValue::from_input(2.0).unwrap());
+        // values are still in SI base units
+        assert_eq!(run!(calc, "1 km + 1 m"), UnitValue::from_input(1001.0).unwrap());
+        assert!(run!(calc, "3 m * 2 s").unitless());
+        assert_eq!(calc.command("units strict"), Some(Ok(String::new())));
+        assert_eq!(calc.run("1 m + 1 s"), Err(CalculatorError::UnitError));
+        assert!(calc.command("units").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_angle_mode() {
+        let mut calc = Calculator::new();
+        assert!((run!(calc, "sin(pi/2)").as_float() - 1.0).abs() < 1e-12);
+        assert_eq!(calc.command("angles degrees"), Some(Ok(String::new())));
+        assert!((run!(calc, "sin(90)").as_float() - 1.0).abs() < 1e-12);
+        assert!((run!(calc, "cos(60)").as_float() - 0.5).abs() < 1e-12);
+        assert!((run!(calc, "asin(1)").as_float() - 90.0).abs() < 1e-12);
+        assert!((run!(calc, "atan2(1, 1)").as_float() - 45.0).abs() < 1e-12);
+        // an angle written with a unit, or converted with deg(), is already in radians
+        assert!((run!(calc, "sin(90 deg)").as_float() - 1.0).abs() < 1e-12);
+        assert!((run!(calc, "sin(pi/2 rad)").as_float() - 1.0).abs() < 1e-12);
+        assert!((run!(calc, "cos(-60 deg)").as_float() - 0.5).abs() < 1e-12);
+        assert!((run!(calc, "sin(deg(30))").as_float() - 0.5).abs() < 1e-12);
+        assert!((run!(calc, "tan(45 deg + 0)").as_float() - 1.0).abs() < 1e-12);
+        // but a variable has lost its unit, so it is in degrees
+        run!(calc, "x = pi/2 rad");
+        assert!((run!(calc, "sin(x)").as_float() - consts::FRAC_PI_2.to_radians().sin()).abs() < 1e-12);
+        // other functions are unaffected
+        assert!((run!(calc, "exp(1)").as_float() - consts::E).abs() < 1e-12);
+        assert_eq!(calc.command("angles radians"), Some(Ok(String::new())));
+        assert!((run!(calc, "asin(1)").as_float() - consts::FRAC_PI_2).abs() < 1e-12);
+        assert!(calc.command("angles gradians").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_dms() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("angles degrees"), Some(Ok(String::new())));
+        assert_eq!(run!(calc, "40d30m"), UnitValue::from_input(40.5).unwrap());
+        assert_eq!(format!("{}", run!(calc, "40d30m15s")), "9721/240");
+        assert_eq!(run!(calc, "40°30'15\""), run!(calc, "40d30m15s"));
+        assert_eq!(run!(calc, "40°"), UnitValue::from_input(40.0).unwrap());
+        assert_eq!(run!(calc, "40°30′"), UnitValue::from_input(40.5).unwrap());
+        assert_eq!(run!(calc, "-10d0m36s"), UnitValue::from_input(-10.01).unwrap());
+        assert!((run!(calc, "sin(30d0m)").as_float() - 0.5).abs() < 1e-10);
+        // fractional seconds
+        assert!((run!(calc, "0d0m1.5s").as_float() - 1.5 / 3600.0).abs() < 1e-12);
+        // in radian mode, the angle is in radians
+        assert_eq!(calc.command("angles radians"), Some(Ok(String::new())));
+        assert!((run!(calc, "40d30m").as_float() - 40.5f64.to_radians()).abs() < 1e-12);
+        assert!((run!(calc, "sin(30°)").as_float() - 0.5).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_rounding_mode() {
+        let mut calc = Calculator::new();
+        let n = |a| UnitValue::from_input(a).unwrap();
+        // (mode, round(2.5), round(3.5), round(-2.5))
+        for &(mode, a, b, c) in &[("half-even", 2.0, 4.0, -2.0), ("half-up", 3.0, 4.0, -2.0), ("half-away", 3.0, 4.0, -3.0)] {
+            assert_eq!(calc.command(&format!("rounding {}", mode)), Some(Ok(String::new())));
+            assert_eq!(run!(calc, "round(2.5)"), n(a), "{}", mode);
+            assert_eq!(run!(calc, "round(3.5)"), n(b), "{}", mode);
+            assert_eq!(run!(calc, "round(-2.5)"), n(c), "{}", mode);
+            assert_eq!(run!(calc, "round(7/3)"), n(2.0), "{}", mode);
+        }
+        assert_eq!(calc.run("round(2.5 m)"), Err(CalculatorError::UnitError));
+        assert!(calc.command("rounding down").unwrap().is_err());
+        // significant figures are rounded the same way
+        assert_eq!(calc.command("sigfigs on"), Some(Ok(String::new())));
+        assert_eq!(calc.command("rounding half-even"), Some(Ok(String::new())));
+        let val = run!(calc, "1.25 * 1.0");
+        assert_eq!(calc.format("1.25 * 1.0", &val), "1.2");
+        assert_eq!(calc.command("rounding half-away"), Some(Ok(String::new())));
+        let val = run!(calc, "1.25 * 1.0");
+        assert_eq!(calc.format("1.25 * 1.0", &val), "1.3");
+        // and so are decimals
+        assert_eq!(calc.command("sigfigs off"), Some(Ok(String::new())));
+        calc.precision.decimals = Some(2);
+        let val = run!(calc, "0.125 + 0 pi");
+        assert_eq!(calc.format("0.125 + 0 pi", &val), "0.13");
+        assert_eq!(calc.command("rounding half-even"), Some(Ok(String::new())));
+        assert_eq!(calc.format("0.125 + 0 pi", &val), "0.12");
+        // the default is half-even
+        assert_eq!(Calculator::new().precision.rounding, RoundingMode::HalfEven);
+    }
+
+    #[test]
+    fn test_load_config() {
+        let mut calc = Calculator::new();
+        let config = "# preferences\nangles degrees\n\ndisplay decimal\nlocale fr\nmaxden 0\n";
+        assert_eq!(calc.load_config(config.as_bytes()).unwrap(),
+                   vec!["line 5: unknown setting locale fr".to_owned(), "line 6: usage: maxden N|off".to_owned()]);
+        assert_eq!(calc.angles, AngleMode::Degrees);
+        assert_eq!(calc.display, DisplayMode::Decimal);
+        assert_eq!(calc.max_denominator, None);
+        assert!((run!(calc, "sin(30)").as_float() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_max_degree() {
+        let mut calc = Calculator::new();
+        run!(calc, "(2 m)^6");
+        assert!(calc.warnings.is_empty());
+        assert_eq!(calc.command("maxdegree 5"), Some(Ok(String::new())));
+        run!(calc, "(2 m)^6");
+        assert_eq!(calc.warnings, vec!["the unit m^6 has degree 6, which is unusual".to_owned()]);
+        run!(calc, "m^5");
+        assert!(calc.warnings.is_empty());
+        // negative exponents count too
+        run!(calc, "kg / m^3 / s^3");
+        assert_eq!(calc.warnings.len(), 1);
+        assert_eq!(calc.command("maxdegree off"), Some(Ok(String::new())));
+        run!(calc, "m^6");
+        assert!(calc.warnings.is_empty());
+        assert!(calc.command("maxdegree -1").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_cancel_factors() {
+        use Expression as E;
+        let mut calc = Calculator::new();
+        let mut eval = |line: &str| match raw_input(line.as_bytes()) {
+            IResult::Done(_, expr) => calc.evaluate(expr),
+            _ => panic!("{:?} does not parse", line),
+        };
+        let x = || Box::new(E::Var("x".to_owned()));
+        let y = || Box::new(E::Var("y".to_owned()));
+        let n = |a| Box::new(E::Value(UnitValue::from_input(a).unwrap()));
+        assert_eq!(eval("(2x)/4"), E::Div(x(), n(2.0)));
+        assert_eq!(eval("(x*6)/4"), E::Div(Box::new(E::Mul(n(3.0), x())), n(2.0)));
+        assert_eq!(eval("(4x)/2"), E::Mul(n(2.0), x()));
+        assert_eq!(eval("(2x)/2"), *x());
+        assert_eq!(eval("(2x)/-2"), E::Neg(x()));
+        // assumes x is not zero
+        assert_eq!(eval("(x*y)/x"), *y());
+        assert_eq!(eval("(y*x)/x"), *y());
+        // units are kept in the coefficient
+        assert_eq!(eval("(6 m x)/(2 s)"), E::Mul(Box::new(E::Value(UnitValue::meters(3.0).unwrap().div(&UnitValue::seconds(1.0).unwrap()).unwrap())), x()));
+        // other fractions are left alone
+        assert_eq!(eval("(x*y)/(2x)"), E::Div(Box::new(E::Mul(x(), y())), Box::new(E::Mul(n(2.0), x()))));
+        assert_eq!(eval("(2x)/0"), E::Div(Box::new(E::Mul(n(2.0), x())), n(0.0)));
+    }
+
+    #[test]
+    fn test_rpn_mode() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.command("rpn on"), Some(Ok(String::new())));
+        assert_eq!(run!(calc, "3 4 + 2 *"), UnitValue::from_input(14.0).unwrap());
+        assert_eq!(calc.run("3 +"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc.error_position, None);
+        assert_eq!(calc.run("3 4 + 2"), Err(CalculatorError::SyntaxError));
+        // infix input is not accepted
+        assert_eq!(calc.run("3 + 4"), Err(CalculatorError::SyntaxError));
+        calc.variables.insert("x".to_owned(), UnitValue::from_input(5.0).unwrap());
+        assert_eq!(run!(calc, "x 2 ^"), UnitValue::from_input(25.0).unwrap());
+        assert_eq!(calc.command("rpn off"), Some(Ok(String::new())));
+        assert_eq!(run!(calc, "3 + 4"), UnitValue::from_input(7.0).unwrap());
+        assert!(calc.command("rpn").unwrap().is_err());
+    }
+}
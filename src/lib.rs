@@ -0,0 +1,2203 @@
+//! The library crate. Contains the parser and ties together all the submodules; the
+//! binary in `main.rs` is a thin REPL on top of it.
+//!
+//! Note regarding documentation: Rust does not currently support documentation on items
+//! generated by macros, so many functions in this module are undocumented. Read the source
+//! code to see a few notes on these items.
+
+#![feature(box_patterns)]
+#![feature(plugin)]
+#![plugin(phf_macros)]
+#[macro_use]
+extern crate nom;
+extern crate phf;
+
+use nom::{multispace, alpha, eof, IResult};
+
+use std::str;
+use std::fmt;
+use std::cmp;
+
+pub mod rational;
+pub mod value;
+pub mod unit;
+pub mod uval;
+pub mod units;
+pub mod calc;
+pub mod repl;
+pub mod numtheory;
+pub mod measurement;
+pub mod sigfig;
+
+use rational::AsFloat;
+
+/// A mathematical expression. Can be either known or unknown (e.g. a variable that has not
+/// been defined.)
+///
+/// The parser produces an unevaluated expression; use `simplify` or `Calculator::evaluate`
+/// to turn it into a value.
+#[derive(Clone)]
+pub enum Expression {
+    /// A known value (with unit).
+    Value(uval::UnitValue),
+    /// Units as they were written (e.g. `ft/s^2`), and their value. The text is kept so the
+    /// result can be shown in the unit system it was written in.
+    Units(uval::UnitValue, String),
+    /// A number as the user typed it (underscores removed); not yet converted to a value.
+    Number(String),
+    /// A variable; its value is looked up when the expression is evaluated.
+    Var(String),
+    /// An error has occurred; errors propagate to all expressions in which it is involved.
+    Error(value::ArithmeticError),
+    /// Exponentiation, a^b
+    Exp(Box<Expression>, Box<Expression>),
+    /// Multiplication, a*b
+    Mul(Box<Expression>, Box<Expression>),
+    /// Division, a/b
+    Div(Box<Expression>, Box<Expression>),
+    /// Addition, a+b
+    Add(Box<Expression>, Box<Expression>),
+    /// Subtraction, a-b
+    Sub(Box<Expression>, Box<Expression>),
+    /// Negation, -a
+    Neg(Box<Expression>),
+    /// Factorial, a!
+    Fact(Box<Expression>),
+    /// A value with an uncertainty, a ± b
+    PlusMinus(Box<Expression>, Box<Expression>),
+    /// A known value with an uncertainty (boxed, since it holds two values and would otherwise
+    /// double the size of every expression)
+    Measured(Box<measurement::Measurement>),
+    /// Function call, f(a,b,c...); the function is looked up by name when the expression is
+    /// evaluated, so it may be a builtin or a function registered with the calculator.
+    Call(String, Vec<Expression>),
+}
+
+/// A function takes the values of its arguments and returns a value or an error.
+// a Box is an owned pointer (a function is not a concrete type)
+// a Vec is like an ArrayList
+pub type Function = Box<Fn(Vec<uval::UnitValue>) -> Result<uval::UnitValue, value::ArithmeticError> + Send>;
+
+/// The arity of a function that takes any (nonzero) number of arguments
+pub const VARIADIC: usize = 0;
+
+/// Whether a function with the given arity can take `n` arguments
+pub fn arity_matches(arity: usize, n: usize) -> bool {
+    arity == n || (arity == VARIADIC && n > 0)
+}
+
+/// An error that can occur while running a line of input.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum CalculatorError {
+    /// Caused by division by zero
+    DivideByZeroError,
+    /// Caused by an invalid argument
+    DomainError,
+    /// Caused by overflow
+    OverflowError,
+    /// Incompatible units or invalid use of units
+    UnitError,
+    /// The input could not be parsed
+    SyntaxError,
+}
+
+impl fmt::Display for CalculatorError {
+    /// Describe the error for users (e.g. `division by zero`)
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match *self {
+            CalculatorError::DivideByZeroError => "division by zero",
+            CalculatorError::DomainError => "argument out of domain",
+            CalculatorError::OverflowError => "numeric overflow",
+            CalculatorError::UnitError => "incompatible units",
+            CalculatorError::SyntaxError => "syntax error",
+        })
+    }
+}
+
+impl CalculatorError {
+    /// A short code for the error that will not change, for scripts (e.g. `E_DIVZERO`)
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CalculatorError::DivideByZeroError => "E_DIVZERO",
+            CalculatorError::DomainError => "E_DOMAIN",
+            CalculatorError::OverflowError => "E_OVERFLOW",
+            CalculatorError::UnitError => "E_UNIT",
+            CalculatorError::SyntaxError => "E_SYNTAX",
+        }
+    }
+}
+
+impl From<value::ArithmeticError> for CalculatorError {
+    /// Convert an arithmetic error (from evaluating an expression)
+    fn from(e: value::ArithmeticError) -> CalculatorError {
+        match e {
+            value::ArithmeticError::DivideByZeroError => CalculatorError::DivideByZeroError,
+            value::ArithmeticError::DomainError => CalculatorError::DomainError,
+            value::ArithmeticError::OverflowError => CalculatorError::OverflowError,
+            value::ArithmeticError::UnitError => CalculatorError::UnitError,
+        }
+    }
+}
+
+/// The number of each kind of operation in an expression
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct OpCounts {
+    /// Exponentiations
+    pub exp: usize,
+    /// Multiplications (including implied multiplication)
+    pub mul: usize,
+    /// Divisions
+    pub div: usize,
+    /// Additions
+    pub add: usize,
+    /// Subtractions
+    pub sub: usize,
+    /// Negations
+    pub neg: usize,
+    /// Factorials
+    pub fact: usize,
+    /// Uncertainties (±)
+    pub plus_minus: usize,
+    /// Function calls
+    pub call: usize,
+}
+
+impl OpCounts {
+    /// Total number of operations
+    pub fn total(&self) -> usize {
+        self.exp + self.mul + self.div + self.add + self.sub + self.neg + self.fact + self.plus_minus + self.call
+    }
+}
+
+/// A token of an expression in postfix (RPN) order, e.g. `2 3 4 * +` for `2 + 3 * 4`
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpnToken {
+    /// An expression with no operations: a value, number, variable, or error
+    Operand(Expression),
+    /// A binary operator (`+`, `-`, `*`, `/`, `^`, or `±`), applied to the top two operands
+    Operator(char),
+    /// Negation of the top operand
+    Neg,
+    /// Factorial of the top operand
+    Fact,
+    /// A function applied to the given number of operands
+    Call(String, usize),
+}
+
+impl fmt::Display for RpnToken {
+    /// Operators are shown as they are written; a call shows its number of arguments (e.g.
+    /// `atan2/2`) and negation is `neg`, to tell it apart from subtraction
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            &RpnToken::Operand(ref a) => write!(f, "{}", a),
+            &RpnToken::Operator(op) => write!(f, "{}", op),
+            &RpnToken::Neg => write!(f, "neg"),
+            &RpnToken::Fact => write!(f, "!"),
+            &RpnToken::Call(ref name, n) => write!(f, "{}/{}", name, n),
+        }
+    }
+}
+
+/// Types that can be converted to a value implement this trait.
+pub trait ToValue {
+    /// Convert this object to a value or return an error.
+    fn to_value(&self) -> Result<uval::UnitValue, value::ArithmeticError>;
+}
+
+/// Make a Value Expression from a ToValue type
+#[inline]
+pub fn make_value<V: ToValue>(v: V) -> Expression {
+    // Call Expression::Value on a successful result or call Expression::Error on error
+    v.to_value().map(Expression::Value).unwrap_or_else(Expression::Error)
+}
+
+/// This is only called when handling user input. It treats some
+/// numbers that can be handled exactly as fractions rather than
+/// floating-point inexact numbers.
+#[inline]
+pub fn input_value(v: f64) -> Expression {
+    // call the from_input method to convert rather than from_float
+    make_value(uval::UnitValue::from_input(v))
+}
+
+impl ToValue for Result<uval::UnitValue, value::ArithmeticError> {
+    #[inline]
+    fn to_value(&self) -> Result<uval::UnitValue, value::ArithmeticError> {
+        *self
+    }
+}
+
+impl ToValue for uval::UnitValue {
+    #[inline]
+    fn to_value(&self) -> Result<uval::UnitValue, value::ArithmeticError> {
+        Ok(*self)
+    }
+}
+
+impl ToValue for f64 {
+    #[inline]
+    fn to_value(&self) -> Result<uval::UnitValue, value::ArithmeticError> {
+        // this does not convert to approximate floats as rational numbers
+        uval::UnitValue::from_float(*self)
+    }
+}
+
+/// Expressions can be compared for equality
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        match (self, other) {
+            (&Expression::Value(ref a), &Expression::Value(ref b)) => a == b,
+            (&Expression::Units(ref a, ref b), &Expression::Units(ref c, ref d)) => a == c && b == d,
+            (&Expression::Number(ref a), &Expression::Number(ref b)) => a == b,
+            (&Expression::Var(ref a), &Expression::Var(ref b)) => a == b,
+            (&Expression::Exp(ref a, ref b), &Expression::Exp(ref c, ref d)) => a == c && b == d,
+            (&Expression::Mul(ref a, ref b), &Expression::Mul(ref c, ref d)) => a == c && b == d,
+            (&Expression::Div(ref a, ref b), &Expression::Div(ref c, ref d)) => a == c && b == d,
+            (&Expression::Add(ref a, ref b), &Expression::Add(ref c, ref d)) => a == c && b == d,
+            (&Expression::Sub(ref a, ref b), &Expression::Sub(ref c, ref d)) => a == c && b == d,
+            (&Expression::Neg(ref a), &Expression::Neg(ref b)) => a == b,
+            (&Expression::Fact(ref a), &Expression::Fact(ref b)) => a == b,
+            (&Expression::PlusMinus(ref a, ref b), &Expression::PlusMinus(ref c, ref d)) => a == c && b == d,
+            (&Expression::Measured(ref a), &Expression::Measured(ref b)) => a == b,
+            (&Expression::Call(ref a, ref b), &Expression::Call(ref c, ref d)) => a == c && b == d,
+            (&Expression::Error(ref a), &Expression::Error(ref b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+/// Debug printing
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            &Expression::Value(ref a) => write!(f, "Expression::Value({:?})", a),
+            &Expression::Units(ref a, ref b) => write!(f, "Expression::Units({:?}, {:?})", a, b),
+            &Expression::Number(ref a) => write!(f, "Expression::Number({:?})", a),
+            &Expression::Var(ref a) => write!(f, "Expression::Var({:?})", a),
+            &Expression::Exp(ref a, ref b) => write!(f, "Expression::Exp({:?}, {:?})", a, b),
+            &Expression::Mul(ref a, ref b) => write!(f, "Expression::Mul({:?}, {:?})", a, b),
+            &Expression::Div(ref a, ref b) => write!(f, "Expression::Div({:?}, {:?})", a, b),
+            &Expression::Add(ref a, ref b) => write!(f, "Expression::Add({:?}, {:?})", a, b),
+            &Expression::Sub(ref a, ref b) => write!(f, "Expression::Sub({:?}, {:?})", a, b),
+            &Expression::Neg(ref a) => write!(f, "Expression::Neg({:?})", a),
+            &Expression::Fact(ref a) => write!(f, "Expression::Fact({:?})", a),
+            &Expression::PlusMinus(ref a, ref b) => write!(f, "Expression::PlusMinus({:?}, {:?})", a, b),
+            &Expression::Measured(ref a) => write!(f, "Expression::Measured({:?})", a),
+            &Expression::Call(ref a, ref b) => write!(f, "Expression::Call({:?}, {:?})", a, b),
+            &Expression::Error(ref a) => write!(f, "Expression::Error({:?})", a),
+        }
+    }
+}
+
+/// Display an Expression as a string (equivalent of toString())
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            // a Value is printed as is
+            &Expression::Value(ref a) => write!(f, "{}", a),
+            &Expression::Measured(ref a) => write!(f, "{}", a),
+            // so is a number, a variable name, or units
+            &Expression::Number(ref a) | &Expression::Var(ref a) | &Expression::Units(_, ref a) => write!(f, "{}", a),
+            // Error does not have a Display implementation yet
+            &Expression::Error(ref a) => write!(f, "{:?}", a),
+            _ => write!(f, "unknown"),
+        }
+    }
+}
+
+// Expression methods
+impl Expression {
+    /// Is this expression a known value
+    #[inline]
+    pub fn is_known(&self) -> bool {
+        match self {
+            &Expression::Value(_) => true,
+            _ => false
+        }
+    }
+    /// Is this expression an error
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        match self {
+            &Expression::Error(_) => true,
+            _ => false
+        }
+    }
+    /// Is this expression written using only integer numbers (e.g. 7/2 but not 7.0/2 or pi/2)
+    pub fn is_integral(&self) -> bool {
+        match self {
+            &Expression::Number(ref a) => !a.contains(|c| c == '.' || c == 'e' || c == 'E'),
+            &Expression::Exp(ref a, ref b) |
+            &Expression::Mul(ref a, ref b) |
+            &Expression::Div(ref a, ref b) |
+            &Expression::Add(ref a, ref b) |
+            &Expression::Sub(ref a, ref b) |
+            &Expression::PlusMinus(ref a, ref b) => a.is_integral() && b.is_integral(),
+            &Expression::Neg(ref a) | &Expression::Fact(ref a) => a.is_integral(),
+            _ => false
+        }
+    }
+    /// Count the operations in an (unevaluated) expression
+    pub fn count_ops(&self) -> OpCounts {
+        let mut counts = OpCounts::default();
+        self.add_op_counts(&mut counts);
+        counts
+    }
+    /// Add the operations in this expression to the counts
+    fn add_op_counts(&self, counts: &mut OpCounts) {
+        match self {
+            &Expression::Exp(ref a, ref b) => { counts.exp += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Mul(ref a, ref b) => { counts.mul += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Div(ref a, ref b) => { counts.div += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Add(ref a, ref b) => { counts.add += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Sub(ref a, ref b) => { counts.sub += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Neg(ref a) => { counts.neg += 1; a.add_op_counts(counts) },
+            &Expression::Fact(ref a) => { counts.fact += 1; a.add_op_counts(counts) },
+            &Expression::PlusMinus(ref a, ref b) => { counts.plus_minus += 1; a.add_op_counts(counts); b.add_op_counts(counts) },
+            &Expression::Call(_, ref args) => {
+                counts.call += 1;
+                for a in args {
+                    a.add_op_counts(counts);
+                }
+            },
+            _ => ()
+        }
+    }
+    /// The expression in postfix (RPN) order: each operation comes after its operands
+    pub fn to_rpn(&self) -> Vec<RpnToken> {
+        let mut tokens = Vec::new();
+        self.add_rpn_tokens(&mut tokens);
+        tokens
+    }
+    /// Add the tokens of this expression (in postfix order) to the list
+    fn add_rpn_tokens(&self, tokens: &mut Vec<RpnToken>) {
+        let (a, b, op) = match self {
+            &Expression::Exp(ref a, ref b) => (a, b, '^'),
+            &Expression::Mul(ref a, ref b) => (a, b, '*'),
+            &Expression::Div(ref a, ref b) => (a, b, '/'),
+            &Expression::Add(ref a, ref b) => (a, b, '+'),
+            &Expression::Sub(ref a, ref b) => (a, b, '-'),
+            &Expression::PlusMinus(ref a, ref b) => (a, b, '±'),
+            &Expression::Neg(ref a) => { a.add_rpn_tokens(tokens); return tokens.push(RpnToken::Neg) },
+            &Expression::Fact(ref a) => { a.add_rpn_tokens(tokens); return tokens.push(RpnToken::Fact) },
+            &Expression::Call(ref name, ref args) => {
+                for a in args {
+                    a.add_rpn_tokens(tokens);
+                }
+                return tokens.push(RpnToken::Call(name.clone(), args.len()))
+            },
+            operand => return tokens.push(RpnToken::Operand(operand.clone())),
+        };
+        a.add_rpn_tokens(tokens);
+        b.add_rpn_tokens(tokens);
+        tokens.push(RpnToken::Operator(op));
+    }
+    /// Extract a value or panic! (forcibly terminates the thread)
+    #[inline]
+    pub fn extract_value(&self) -> uval::UnitValue {
+        match self {
+            &Expression::Value(a) | &Expression::Units(a, _) => a,
+            _ => panic!("extract value of unknown")
+        }
+    }
+    /// Extract a floating-point value or panic!
+    #[inline]
+    pub fn extract_float(&self) -> f64 {
+        match self {
+            &Expression::Value(a) | &Expression::Units(a, _) => a.as_float(),
+            _ => panic!("extract value of unknown")
+        }
+    }
+}
+
+/// Lookup a unary function by name (for convenience)
+pub fn get_unary_function(res: &[u8]) -> Option<Box<Fn(f64) -> f64 + Send>> {
+    match res {
+        b"sin" => Some(Box::new(f64::sin)),
+        b"cos" => Some(Box::new(f64::cos)),
+        b"tan" => Some(Box::new(f64::tan)),
+        b"asin" => Some(Box::new(f64::asin)),
+        b"acos" => Some(Box::new(f64::acos)),
+        b"atan" => Some(Box::new(f64::atan)),
+        b"sinh" => Some(Box::new(f64::sinh)),
+        b"cosh" => Some(Box::new(f64::cosh)),
+        b"tanh" => Some(Box::new(f64::tanh)),
+        b"asinh" => Some(Box::new(f64::asinh)),
+        b"acosh" => Some(Box::new(f64::acosh)),
+        // atanh(±1) is infinite, but that is outside the domain rather than an overflow
+        // (NaN becomes a DomainError)
+        b"atanh" => Some(Box::new(|x: f64| if x.abs() < 1.0 { x.atanh() } else { std::f64::NAN })),
+        b"sqrt" => Some(Box::new(f64::sqrt)),
+        b"cbrt" => Some(Box::new(f64::cbrt)),
+        b"ln" => Some(Box::new(f64::ln)),
+        b"log10" => Some(Box::new(f64::log10)),
+        b"log2" => Some(Box::new(f64::log2)),
+        b"exp" => Some(Box::new(f64::exp)),
+        // an angle in degrees in radians, and the reverse (as functions, these take precedence
+        // over the units deg and rad, e.g. rad(2) is not 2 rad)
+        b"deg" => Some(Box::new(f64::to_radians)),
+        b"rad" => Some(Box::new(f64::to_degrees)),
+        _ => None
+    }
+}
+
+/// Get a function that keeps exact arguments exact by name, and how many arguments it takes
+pub fn get_exact_function(res: &[u8]) -> Option<(usize, Function)> {
+    use uval::UnitValue;
+    use rational::{Rational, RoundingMode};
+    /// Round a unitless value (rounding depends on the unit, so other values are an error)
+    fn rounding(a: &UnitValue, exact: fn(&Rational) -> Rational, inexact: fn(f64) -> f64)
+            -> Result<UnitValue, value::ArithmeticError> {
+        if a.unitless() {
+            Ok(UnitValue::with_unit(a.value.round_with(exact, inexact), a.unit))
+        } else {
+            Err(value::ArithmeticError::UnitError)
+        }
+    }
+    /// The smallest or largest argument (depending on `pick`); they must have the same unit
+    fn extreme(a: Vec<UnitValue>, pick: std::cmp::Ordering) -> Result<UnitValue, value::ArithmeticError> {
+        let mut best = a[0];
+        for x in &a[1..] {
+            match x.partial_cmp(&best) {
+                Some(o) if o == pick => best = *x,
+                Some(_) => (),
+                None => return Err(value::ArithmeticError::UnitError),
+            }
+        }
+        Ok(best)
+    }
+    let arity = match res {
+        b"gcd" | b"nCr" | b"nPr" | b"of" => 2,
+        b"lerp" | b"dms" => 3,
+        b"min" | b"max" => VARIADIC,
+        _ => 1,
+    };
+    let f: Option<Function> = match res {
+        b"abs" => Some(Box::new(|a: Vec<UnitValue>| Ok(if a[0].value.is_negative() { -a[0] } else { a[0] }))),
+        b"sign" => Some(Box::new(|a: Vec<UnitValue>| UnitValue::from_input(
+                    if a[0].is_zero() { 0.0 } else if a[0].value.is_negative() { -1.0 } else { 1.0 }))),
+        b"floor" => Some(Box::new(|a: Vec<UnitValue>| rounding(&a[0], Rational::floor, f64::floor))),
+        b"ceil" => Some(Box::new(|a: Vec<UnitValue>| rounding(&a[0], Rational::ceil, f64::ceil))),
+        // halfway cases are rounded to even (a Calculator rounds them according to its setting)
+        b"round" => Some(Box::new(|a: Vec<UnitValue>| rounding(&a[0], |r| r.round_with_mode(RoundingMode::HalfEven),
+                                                               |f| RoundingMode::HalfEven.round_float(f)))),
+        b"trunc" => Some(Box::new(|a: Vec<UnitValue>| rounding(&a[0], Rational::trunc, f64::trunc))),
+        b"factorial" => Some(Box::new(|a: Vec<UnitValue>| a[0].factorial())),
+        // x% (a percentage of a unit-bearing value would be ambiguous, e.g. 50% m)
+        b"percent" => Some(Box::new(|a: Vec<UnitValue>| if a[0].unitless() {
+            UnitValue::from_input(100.0).and_then(|h| a[0].div(&h))
+        } else {
+            Err(value::ArithmeticError::UnitError)
+        })),
+        // an angle in degrees, minutes, and seconds, as degrees (e.g. 40d30m is 40.5)
+        b"dms" => Some(Box::new(|a: Vec<UnitValue>| {
+            if !a.iter().all(UnitValue::unitless) {
+                return Err(value::ArithmeticError::UnitError)
+            }
+            let minutes = try!(a[2].div(&try!(UnitValue::from_input(60.0))).and_then(|s| a[1].add(&s)));
+            minutes.div(&try!(UnitValue::from_input(60.0))).and_then(|m| a[0].add(&m))
+        })),
+        // a of b (e.g. 20% of 50 m), where a is a unitless fraction
+        b"of" => Some(Box::new(|a: Vec<UnitValue>| if a[0].unitless() {
+            a[0].mul(&a[1])
+        } else {
+            Err(value::ArithmeticError::UnitError)
+        })),
+        b"gcd" => Some(Box::new(|a: Vec<UnitValue>| numtheory::integer(&a[0])
+                                .and_then(|x| numtheory::integer(&a[1]).map(|y| numtheory::gcd(x, y)))
+                                .and_then(numtheory::integer_value))),
+        // a + (b - a) t; a and b must have the same unit (even if one is zero), and t is unitless
+        // (it is not limited to [0, 1])
+        b"lerp" => Some(Box::new(|a: Vec<UnitValue>| {
+            if a[0].unit != a[1].unit || !a[2].unitless() {
+                return Err(value::ArithmeticError::UnitError)
+            }
+            a[1].sub(&a[0]).and_then(|d| d.mul(&a[2])).and_then(|d| a[0].add(&d))
+        })),
+        b"nCr" => Some(Box::new(|a: Vec<UnitValue>| numtheory::integer(&a[0])
+                                .and_then(|n| numtheory::integer(&a[1]).and_then(|r| numtheory::combinations(n, r)))
+                                .and_then(numtheory::integer_value))),
+        b"nPr" => Some(Box::new(|a: Vec<UnitValue>| numtheory::integer(&a[0])
+                                .and_then(|n| numtheory::integer(&a[1]).and_then(|r| numtheory::permutations(n, r)))
+                                .and_then(numtheory::integer_value))),
+        b"min" => Some(Box::new(|a: Vec<UnitValue>| extreme(a, std::cmp::Ordering::Less))),
+        b"max" => Some(Box::new(|a: Vec<UnitValue>| extreme(a, std::cmp::Ordering::Greater))),
+        b"factor" => Some(Box::new(|a: Vec<UnitValue>| numtheory::positive_integer(&a[0])
+                                   .and_then(|n| numtheory::integer_value(numtheory::factorize(n).len() as i64)))),
+        b"isprime" => Some(Box::new(|a: Vec<UnitValue>| numtheory::positive_integer(&a[0])
+                                    .and_then(|n| numtheory::integer_value(numtheory::is_prime(n) as i64)))),
+        _ => None
+    };
+    f.map(|f| (arity, f))
+}
+
+/// Get a function by name (including multi-argument functions), and how many arguments it
+/// takes
+pub fn get_function(res: &[u8]) -> Option<(usize, Function)> {
+    /// The result of a floating-point function is inexact
+    fn irrational(f: f64) -> Result<uval::UnitValue, value::ArithmeticError> {
+        uval::UnitValue::inexact(f, value::InexactReason::IrrationalFunction)
+    }
+    // functions that keep exactness take precedence
+    if let Some(f) = get_exact_function(res) {
+        return Some(f)
+    }
+    // unary functions next
+    if let Some(f) = get_unary_function(res) {
+        // a root also takes the root of the unit (e.g. sqrt(4 m^2) is 2 m), while the angle
+        // conversions, logarithms, and exp only take plain numbers
+        let root = match res { b"sqrt" => Some(2), b"cbrt" => Some(3), _ => None };
+        let unitless = match res { b"deg" | b"rad" | b"ln" | b"log10" | b"log2" | b"exp" => true, _ => false };
+        // logarithms are only defined for positive numbers (the logarithm of 0 would be -inf)
+        let logarithm = match res { b"ln" | b"log10" | b"log2" => true, _ => false };
+        return Some((1, Box::new(move |a: Vec<uval::UnitValue>| if let Some(n) = root {
+            let unit = try!(rational::Rational::new(1, n).and_then(|e| a[0].unit.mul(&e)).map_err(value::ArithmeticError::from));
+            irrational(f(a[0].value.as_float())).map(|v| uval::UnitValue { value: v.value, unit: unit })
+        } else if unitless && !a[0].unitless() {
+            Err(value::ArithmeticError::UnitError)
+        } else if logarithm && a[0].value.as_float() <= 0.0 {
+            Err(value::ArithmeticError::DomainError)
+        } else {
+            irrational(f(a[0].as_float()))
+        })))
+    }
+    // multi-argument functions
+    let f: Option<Function> = match res {
+        b"atan2" => Some(Box::new(|a: Vec<uval::UnitValue>| irrational(a[0].as_float().atan2(a[1].as_float())))),
+        // the logarithm of x to base b, both plain numbers
+        b"log" => Some(Box::new(|a: Vec<uval::UnitValue>| {
+            if !a[0].unitless() || !a[1].unitless() {
+                return Err(value::ArithmeticError::UnitError)
+            }
+            let (x, b) = (a[0].value.as_float(), a[1].value.as_float());
+            if x <= 0.0 || b <= 0.0 || b == 1.0 {
+                Err(value::ArithmeticError::DomainError)
+            } else {
+                irrational(x.log(b))
+            }
+        })),
+        // x^(1/n), which is exact if x is a perfect nth power (and keeps units, e.g. the square
+        // root of m^2 is m); even roots of negative numbers are not real
+        b"root" => Some(Box::new(|a: Vec<uval::UnitValue>| {
+            if a[0].is_zero() {
+                return Err(value::ArithmeticError::DomainError)
+            }
+            uval::UnitValue::from_input(1.0).and_then(|one| one.div(&a[0])).and_then(|e| a[1].pow(&e))
+        })),
+        _ => None
+    };
+    f.map(|f| (2, f))
+}
+
+/// A name of a function or unit may contain letters, numbers, and underscores
+/// (and µ, for the SI prefix)
+#[inline]
+named!(pub name<&[u8]>, recognize!(many1!(alt!(
+            value!((), complete!(one_of!("0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_")))
+          | value!((), complete!(tag!("µ")))))));
+
+/// A parenthetical expression
+// Floor and ceiling brackets (⌊x⌋ and ⌈x⌉) are shorthand for floor(x) and ceil(x), and bars
+// (|x|) for abs(x). Since the same character opens and closes bars, nesting them (e.g.
+// |1 - |x||) is ambiguous and not supported; use abs() instead.
+// A function name is only a call if it is immediately followed by a '(' (e.g. sin(x)).
+// Otherwise (e.g. sin (x) or sin x) the name is treated as a unit or constant, so a unit
+// whose name coincides with a function can still be used without parentheses.
+// Function calls are tried first, so f(x) is always a call if f is a function.
+named!(pub parens<Expression>, alt!(
+    // either an expression in parentheses
+        delimited!(complete!(char!('('))
+      , preceded!(opt!(multispace), expr)
+      , preceded!(opt!(multispace), complete!(char!(')'))))
+    // or an expression in floor or ceiling brackets
+      | map!(delimited!(complete!(tag!("⌊"))
+                      , preceded!(opt!(multispace), expr)
+                      , preceded!(opt!(multispace), complete!(tag!("⌋")))),
+             |e| Expression::Call("floor".to_owned(), vec![e]))
+      | map!(delimited!(complete!(tag!("⌈"))
+                      , preceded!(opt!(multispace), expr)
+                      , preceded!(opt!(multispace), complete!(tag!("⌉")))),
+             |e| Expression::Call("ceil".to_owned(), vec![e]))
+      | map!(delimited!(complete!(char!('|'))
+                      , preceded!(opt!(multispace), expr)
+                      , preceded!(opt!(multispace), complete!(char!('|')))),
+             |e| Expression::Call("abs".to_owned(), vec![e]))
+    // or a function name followed by parentheses and comma-separated arguments
+      | chain!(
+          func: map_opt!(name, function_name)
+        ~ args: delimited!(complete!(char!('(')), preceded!(opt!(multispace), separated_nonempty_list!(delimited!(opt!(multispace), complete!(char!(',')), opt!(multispace)), expr)), preceded!(opt!(multispace), complete!(char!(')')))),
+          || Expression::Call(func, args)
+      )));
+
+/// The name of a function to call: either a builtin function or any other name that could be
+/// a variable (a unit or constant followed by parentheses is a multiplication instead)
+fn function_name(res: &[u8]) -> Option<String> {
+    let is_builtin = get_function(res).is_some();
+    let is_name = res.first().map_or(false, |&c| (c as char).is_alphabetic())
+        && get_unit(res).is_none() && get_numerical_constant(res).is_none();
+    if is_builtin || is_name { stringify_u8(res).ok() } else { None }
+}
+
+/// Recognize integers and numbers with digits on the left side of decimal point (e.g. 57, 2.3)
+#[inline]
+named!(recognize_number1<&[u8]>, recognize!(
+        chain!(decimal
+             ~ preceded!(complete!(char!('.')), opt!(decimal))?
+             ~ preceded!(complete!(one_of!("eE")),
+                   preceded!(opt!(complete!(one_of!("+-"))), decimal))?,
+             || ())));
+/// Recognize numbers with a decimal point followed by digits (e.g. .2, .7)
+#[inline]
+named!(recognize_number2<&[u8]>, recognize!(
+        chain!(complete!(char!('.'))
+             ~ decimal
+             ~ preceded!(complete!(one_of!("eE")),
+                   preceded!(opt!(complete!(one_of!("+-"))), decimal))?,
+             || ())));
+/// Convert a [u8] (char array) to a String
+#[inline]
+fn stringify_u8(res: &[u8]) -> Result<String, str::Utf8Error> {
+    Ok(try!(str::from_utf8(res)).to_owned())
+}
+/// Convert a [u8] to a String and add a 0 at the front (.2 -> 0.2)
+#[inline]
+fn prepend_zero(res: &[u8]) -> Result<String, str::Utf8Error> {
+    let mut s = try!(str::from_utf8(res)).to_owned();
+    s.insert(0, '0');
+    Ok(s)
+}
+
+/// A decimal value (including underscores); underscores are removed
+/// An underscore can be used to provide clarity, e.g. 1_200 for 1,200
+#[inline]
+named!(decimal<()>, value!((), many1!(complete!(one_of!("0123456789_")))));
+
+/// A literal is one of the two number forms above (as a string)
+named!(pub literal<String>, map_res!(map_res!(
+            alt!(recognize_number1 => {stringify_u8}
+               | recognize_number2 => {prepend_zero}),
+            // Remove underscores
+            |a: Result<String, str::Utf8Error>|
+                Ok(try!(a).replace('_', ""))
+                as Result<String, str::Utf8Error>),
+            // then check that it is a valid float (e.g. not just underscores)
+            |a: String| a.parse::<f64>().map(|_| a)));
+
+/// Succeeds (consuming nothing) unless the input continues a name
+fn not_name_char(input: &[u8]) -> IResult<&[u8], ()> {
+    match input.first() {
+        Some(&c) if (c as char).is_alphanumeric() || c == b'_' => IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(3), input)),
+        _ => IResult::Done(input, ()),
+    }
+}
+
+/// An angle in degrees, minutes, and seconds, e.g. `40d30m15s` or `40°30'15"`. Seconds are
+/// optional, and so are minutes when written with `°` (`40d` alone is not an angle).
+named!(pub dms<Expression>, map!(alt!(
+        chain!(d: literal
+             ~ complete!(char!('d'))
+             ~ m: literal
+             ~ complete!(char!('m'))
+             ~ s: opt!(terminated!(literal, complete!(char!('s'))))
+             ~ not_name_char, || (d, Some(m), s))
+      | chain!(d: literal
+             ~ complete!(tag!("°"))
+             ~ m: opt!(terminated!(literal, alt!(complete!(tag!("'")) | complete!(tag!("′")))))
+             ~ s: opt!(terminated!(literal, alt!(complete!(tag!("\"")) | complete!(tag!("″"))))), || (d, m, s))),
+    |(d, m, s): (String, Option<String>, Option<String>)| Expression::Call("dms".to_owned(), vec![
+        Expression::Number(d),
+        Expression::Number(m.unwrap_or("0".to_owned())),
+        Expression::Number(s.unwrap_or("0".to_owned())),
+    ])));
+
+/// An exact fraction written as two integers, e.g. 3:4 for exactly 3/4. Unlike division,
+/// this is exact whatever the calculator mode.
+named!(pub rational_literal<Result<uval::UnitValue, value::ArithmeticError>>, chain!(
+        num: recognize!(decimal)
+      ~ complete!(char!(':'))
+      ~ den: recognize!(decimal), || exact_fraction(num, den)));
+
+/// The exact value of num/den (where both are integers, possibly with underscores)
+fn exact_fraction(num: &[u8], den: &[u8]) -> Result<uval::UnitValue, value::ArithmeticError> {
+    /// Parse an integer that fits in an i32
+    fn integer(a: &[u8]) -> Result<i32, value::ArithmeticError> {
+        str::from_utf8(a).ok()
+            .and_then(|a| a.replace('_', "").parse().ok())
+            .ok_or(value::ArithmeticError::OverflowError)
+    }
+    let (num, den) = (try!(integer(num)), try!(integer(den)));
+    if den == 0 {
+        return Err(value::ArithmeticError::DivideByZeroError)
+    }
+    let r = try!(rational::Rational::new(num, den).map_err(value::ArithmeticError::from));
+    Ok(uval::UnitValue { value: value::Value::Exact(r), unit: unit::Unit::zero() })
+}
+
+/// A number is a literal interpreted as a float
+named!(pub number<f64>, map_res!(literal, |a: String| a.parse()));
+
+/// Look up a numerical constant (unitless)
+pub fn get_numerical_constant(res: &[u8]) -> Option<f64> {
+    match &res {
+        &b"e" => Some(std::f64::consts::E),
+        &b"pi" => Some(std::f64::consts::PI),
+        _ => None
+    }
+}
+
+/// Look up a united value
+pub fn get_unit(res: &[u8]) -> Option<uval::UnitValue> {
+    match str::from_utf8(res) {
+        Ok(a) => units::get(a),
+        Err(_) => None,
+    }
+}
+
+/// A numerical constant consists of only letters
+#[inline]
+named!(pub num_const<f64>, map_opt!(alpha, get_numerical_constant));
+/// A united constant may contains numbers and underscores
+#[inline]
+named!(pub unit_const<uval::UnitValue>, map_opt!(name, get_unit));
+
+/// A variable name starts with a letter and may not be the name of a function
+// (so that e.g. "sin pi" is still an error rather than a variable times pi)
+#[inline]
+named!(pub var_name<String>, map_opt!(recognize!(preceded!(alpha, opt!(name))),
+        |a: &[u8]| if get_function(a).is_some() { None } else { stringify_u8(a).ok() }));
+
+/// A unit raised to an integer power, e.g. `s^2` or `s^-2`
+named!(pub unit_power<uval::UnitValue>, map_opt!(chain!(
+        base: unit_const
+      ~ power: opt!(preceded!(
+                 delimited!(opt!(multispace), alt!(complete!(tag!("^")) | complete!(tag!("**"))), opt!(multispace)),
+                 map_res!(recognize!(preceded!(opt!(complete!(char!('-'))), decimal)), stringify_u8))), || (base, power)),
+    |(base, power): (uval::UnitValue, Option<String>)| match power {
+        None => Some(base),
+        Some(p) => p.replace('_', "").parse::<i32>().ok()
+            .and_then(|p| uval::UnitValue::from_input(p as f64).and_then(|p| base.pow(&p)).ok()),
+    }));
+
+/// A unit expression: units with powers, multiplied or divided from left to right (e.g.
+/// `kg m/s^2`). Units separated by whitespace are multiplied.
+named!(pub unit_expr<uval::UnitValue>, map_opt!(chain!(
+        first: unit_power
+      ~ others: many0!(tuple!(
+                alt!(preceded!(opt!(multispace), complete!(char!('*')))
+                   | preceded!(opt!(multispace), complete!(char!('/')))
+                   | preceded!(opt!(multispace), value!('*', complete!(tag!("×"))))
+                   | preceded!(opt!(multispace), value!('/', complete!(tag!("÷"))))
+                   | value!('*', preceded!(multispace, not_keyword))),
+                preceded!(opt!(multispace), unit_power))), || (first, others)),
+    |(first, others): (uval::UnitValue, Vec<(char, uval::UnitValue)>)|
+        others.into_iter().fold(Some(first), |lhs, (op, rhs)| lhs.and_then(|lhs| match op {
+            '/' => lhs.div(&rhs).ok(),
+            _ => lhs.mul(&rhs).ok(),
+        }))));
+
+/// A number followed by a unit expression, e.g. `9.8 m/s^2` or `7/2 m`, which is a single
+/// quantity whatever the operators around it (so `6 m / 2 s` is 3 m/s). The number may be a
+/// fraction written without spaces, which is then separated from the units by a space (like
+/// implied multiplication, `1/2m` is 1/(2 m)).
+named!(pub quantity<Expression>, chain!(
+        magnitude: alt!(
+            chain!(num: literal ~ complete!(char!('/')) ~ den: literal ~ multispace, ||
+                   Expression::Div(Box::new(Expression::Number(num)), Box::new(Expression::Number(den))))
+          | chain!(r: rational_literal ~ opt!(multispace), || make_value(r))
+          | chain!(n: literal ~ opt!(multispace), || Expression::Number(n)))
+      ~ units: written_units, ||
+    Expression::Mul(Box::new(magnitude), Box::new(units))));
+
+/// A single unit or united constant, and its name
+named!(unit_name<Expression>, map_opt!(name, |a: &[u8]| get_unit(a).and_then(|v| stringify_u8(a).ok().map(|a| Expression::Units(v, a)))));
+
+/// A unit expression, and the text it was written as
+fn written_units(input: &[u8]) -> IResult<&[u8], Expression> {
+    match unit_expr(input) {
+        IResult::Done(rest, units) => IResult::Done(rest, Expression::Units(units, String::from_utf8_lossy(&input[..input.len() - rest.len()]).into_owned())),
+        IResult::Error(e) => IResult::Error(e),
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+    }
+}
+
+/// The innermost level is either parentheticals, numbers, or constants.
+/// Any other name is a variable (units and constants take precedence).
+named!(pub atom<Expression>, alt!(parens
+                            | dms
+                            | quantity
+                            | rational_literal => {make_value}
+                            | literal => {Expression::Number}
+                            | num_const => {make_value}
+                            | unit_name
+                            | var_name => {Expression::Var}));
+
+/// A percent sign that is not followed by an operand, so that `50%` is a percentage but
+/// `10 % 3` is not (it is left for a modulo operator)
+fn percent_sign(input: &[u8]) -> IResult<&[u8], char> {
+    if !input.starts_with(b"%") {
+        return IResult::Error(nom::Err::Position(nom::ErrorKind::Char, input))
+    }
+    let rest = &input[1..];
+    let next = match multispace(rest) {
+        IResult::Done(next, _) => next,
+        _ => rest,
+    };
+    match atom(next) {
+        IResult::Done(..) => IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(2), input)),
+        _ => IResult::Done(rest, '%'),
+    }
+}
+
+/// A factorial (e.g. 5!) or percentage (e.g. 50%) binds more tightly than anything else
+named!(pub postfix<Expression>, chain!(
+       first: atom
+     ~ ops: many0!(alt!(complete!(char!('!')) | percent_sign)), ||
+    ops.into_iter().fold(first, |a, op| match op {
+        '%' => Expression::Call("percent".to_owned(), vec![a]),
+        _ => Expression::Fact(Box::new(a)),
+    })
+));
+
+/// Implied multiplication without spaces has the highest precedence
+// e.g. 1/2pi => 1/(2pi), but 1/2 pi => pi/2
+named!(pub imul<Expression>, chain!(
+       first: postfix
+     ~ others: many0!(postfix), ||
+    others.into_iter().fold(first,
+        |lhs, rhs| Expression::Mul(Box::new(lhs), Box::new(rhs)))
+));
+
+/// How deeply expressions may be nested (e.g. in parentheses or function calls) before the
+/// input is a syntax error; without a limit, the parser could overflow the stack
+pub const MAX_DEPTH: usize = 1000;
+
+/// The stack size of the thread each line is parsed and evaluated on
+// Each level of nesting takes up to about 64 KB of stack in a debug build, so this is enough
+// for `MAX_DEPTH` levels with room to spare (the memory is only used as it is needed).
+const STACK_SIZE: usize = 256 << 20;
+
+/// Call a function on a thread with enough stack for `MAX_DEPTH` levels of nesting, and
+/// return its result
+pub fn with_stack<T: Send, F: FnOnce() -> T + Send>(f: F) -> T {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, f)
+            .expect("could not start a thread");
+        thread.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
+thread_local! {
+    /// How deeply nested the expression being parsed is
+    static DEPTH: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// A unary value such as + and -.
+// Every nested expression (in parentheses, a function call or an exponent) is parsed through
+// here, so this is where the depth is limited.
+pub fn unary(input: &[u8]) -> IResult<&[u8], Expression> {
+    let depth = DEPTH.with(|d| { d.set(d.get() + 1); d.get() });
+    let res = if depth > MAX_DEPTH {
+        IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(1), input))
+    } else {
+        signed(input)
+    };
+    DEPTH.with(|d| d.set(d.get() - 1));
+    res
+}
+
+/// A value with any number of signs in front of it
+// The signs are read in a loop (rather than each one nesting), so a long run of them does
+// not count towards the depth limit.
+named!(signed<Expression>, chain!(
+       signs: many0!(chain!(
+             o: alt!(complete!(char!('+')) | complete!(char!('-')) | value!('-', complete!(tag!("−"))))
+           ~ multispace?, || o))
+     ~ val: exp, ||
+    if signs.iter().filter(|&&o| o == '-').count() % 2 == 1 {
+        Expression::Neg(Box::new(val))
+    } else {
+        val
+    }
+));
+
+/// Exponentiation (right associative), written with ^ or **
+named!(pub exp<Expression>, chain!(
+       lhs: imul
+     ~ rhs: preceded!(preceded!(opt!(multispace), alt!(complete!(tag!("^")) | complete!(tag!("**")))),
+                      preceded!(opt!(multispace), unary))?, ||
+    match (lhs, rhs) {
+        (lhs, None) => lhs,
+        (lhs, Some(b))
+            => Expression::Exp(Box::new(lhs), Box::new(b)),
+    }
+));
+
+/// Keywords for unit conversion (e.g. `5 km in m`)
+pub fn is_keyword(res: &[u8]) -> bool {
+    res == b"in" || res == b"to"
+}
+
+/// Succeeds (without consuming anything) unless the input starts with a conversion keyword
+/// followed by a target, so that `5 km in m` is not parsed as `5 km * in * m`, or with `of`.
+// "in" is also a unit, so it's only a keyword if what follows could start a unit
+// (e.g. "3 in + 2 in" and "5 in" are still inches).
+pub fn not_keyword(input: &[u8]) -> IResult<&[u8], ()> {
+    if let IResult::Done(rest, kw) = name(input) {
+        if kw == b"of" {
+            return IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(0), input))
+        }
+        if is_keyword(kw) {
+            if let IResult::Done(rest, _) = multispace(rest) {
+                if rest.first().map_or(false, |&c| (c as char).is_alphabetic() || c == b'(' || c == b'_') {
+                    return IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(0), input))
+                }
+            }
+        }
+    }
+    IResult::Done(input, ())
+}
+
+/// Succeeds (consuming nothing) unless the input starts with a sign, so that `5 - 3` is not
+/// parsed as `5 * -3`
+pub fn not_sign(input: &[u8]) -> IResult<&[u8], ()> {
+    if input.starts_with(b"+") || input.starts_with(b"-") || input.starts_with("−".as_bytes()) {
+        return IResult::Error(nom::Err::Position(nom::ErrorKind::NoneOf, input))
+    }
+    IResult::Done(input, ())
+}
+
+/// A single factor-term with * or / (or × and ÷) (or whitespace, which is treated as multiplication)
+// Trailing whitespace (e.g. "2 ") passes the peek, but then fails to parse a unary, so the
+// whole facterm fails and the whitespace is left for the opt!(multispace) in input.
+named!(pub facterm<(char, Expression)>,
+        tuple!(alt!(
+               preceded!(opt!(multispace), complete!(char!('*')))
+             | preceded!(opt!(multispace), complete!(char!('/')))
+             | preceded!(opt!(multispace), value!('*', complete!(tag!("×"))))
+             | preceded!(opt!(multispace), value!('/', complete!(tag!("÷"))))
+             | value!('*',
+                      preceded!(multispace,
+                                preceded!(not_keyword, not_sign)))),
+               preceded!(opt!(multispace), unary)));
+
+/// A thing followed by things with operators
+named!(pub fac<Expression>,
+        chain!(first: unary
+             ~ others: many0!(facterm), ||
+    others.into_iter().fold(first, |lhs, (op, rhs)|
+            match op {
+                '*' => Expression::Mul(Box::new(lhs), Box::new(rhs)),
+                '/' => Expression::Div(Box::new(lhs), Box::new(rhs)),
+                _   => Expression::Mul(Box::new(lhs), Box::new(rhs))
+            })
+));
+
+/// A portion of a value, e.g. `20% of 50 m`: the fraction (which must be unitless) times the
+/// value. This binds more loosely than multiplication but more tightly than addition.
+named!(pub portion<Expression>,
+        chain!(first: fac
+             ~ others: many0!(preceded!(
+                       tuple!(multispace, complete!(tag!("of")), multispace),
+                       fac)), ||
+    others.into_iter().fold(first, |lhs, rhs| Expression::Call("of".to_owned(), vec![lhs, rhs]))
+));
+
+/// An expression consists of one portion followed by more terms preceded by + or -.
+named!(pub expr<Expression>,
+        chain!(first: portion
+             ~ others: many0!(tuple!(
+                       preceded!(opt!(multispace),
+                           alt!(complete!(char!('+')) | complete!(char!('-')) | value!('-', complete!(tag!("−")))
+                              | value!('±', complete!(tag!("±"))))),
+                           preceded!(opt!(multispace), portion))), ||
+    others.into_iter().fold(first, |lhs, (op, rhs)|
+            match op {
+                '+' => Expression::Add(Box::new(lhs), Box::new(rhs)),
+                '-' => Expression::Sub(Box::new(lhs), Box::new(rhs)),
+                '±' => Expression::PlusMinus(Box::new(lhs), Box::new(rhs)),
+                _   => Expression::Add(Box::new(lhs), Box::new(rhs))
+            })
+));
+
+/// User input; the whole input must be an expression. The expression is not evaluated.
+// Every token parser is wrapped in complete!, so that reaching the end of the input is a
+// failed match rather than an Incomplete (which nom would propagate instead of backtracking).
+named!(pub raw_input<Expression>, chain!(opt!(multispace) ~ res: expr ~ opt!(multispace) ~ eof, ||{res}));
+
+/// As much of the input as forms an expression; the rest is left unconsumed.
+named!(partial_input<Expression>, chain!(opt!(multispace) ~ res: expr ~ opt!(multispace), ||{res}));
+
+/// Parse as much of the input as possible as an expression (without evaluating it), and
+/// return the result along with the input that was not consumed. On a syntax error, nothing is
+/// consumed.
+///
+/// ```
+/// let (res, rest) = ucalc::parse_partial("2 3 + ");
+/// assert!(res.is_ok());
+/// assert_eq!(rest, "+ ");
+/// ```
+pub fn parse_partial(input: &str) -> (Result<Expression, CalculatorError>, &str) {
+    match partial_input(input.as_bytes()) {
+        IResult::Done(rest, expr) => {
+            let consumed = input.len() - rest.len();
+            if input.is_char_boundary(consumed) {
+                (Ok(expr), &input[consumed..])
+            } else {
+                (Err(CalculatorError::SyntaxError), input)
+            }
+        },
+        _ => (Err(CalculatorError::SyntaxError), input),
+    }
+}
+
+/// The start of a binding: the variable being bound, then `:=` or `=`, and whether it was
+/// `:=` (which also remembers the unit the value was written in)
+named!(binding_var<(String, bool)>, chain!(
+        opt!(multispace)
+      ~ var: var_name
+      ~ opt!(multispace)
+      ~ keep_unit: alt!(value!(true, complete!(tag!(":="))) | value!(false, complete!(tag!("="))))
+      ~ opt!(multispace), || (var, keep_unit)));
+
+/// The variable whose value a line of input shows: the one it binds, or the one it consists
+/// of (e.g. `power` for both `power := 500 W` and `power`)
+named!(pub shown_var<String>, alt!(
+        map!(binding_var, |(var, _)| var)
+      | chain!(opt!(multispace) ~ var: var_name ~ opt!(multispace) ~ eof, || var)));
+
+/// Where the syntax error in a line of input is (as a byte offset): the first character that
+/// is not part of the longest expression at the start of the line (after the variable, if the
+/// line is a binding). If that is an operator, the error is where its operand should be.
+/// None if the whole line can be parsed (e.g. the error is an undefined variable).
+pub fn error_position(input: &str) -> Option<usize> {
+    let start = match binding_var(input.as_bytes()) {
+        IResult::Done(rest, _) => input.len() - rest.len(),
+        _ => 0,
+    };
+    let rest = parse_partial(&input[start..]).1;
+    let pos = input.len() - rest.len();
+    match rest.chars().next() {
+        None if pos > start => None,
+        Some(op) if pos > start && "+-*/^±=:×÷−".contains(op) => {
+            // at the end of the line if the operand is missing
+            let operand = rest[op.len_utf8()..].trim_left();
+            Some(cmp::min(input.len() - operand.len(), input.trim_right().len()))
+        },
+        _ => Some(pos),
+    }
+}
+
+/// A binding of a variable to the value of an expression, e.g. `power := 500 W` (or
+/// `power = 500 W`), and whether it was bound with `:=`. The expression is not evaluated.
+named!(pub binding<(String, Expression, bool)>, chain!(
+        var: binding_var
+      ~ res: expr
+      ~ opt!(multispace)
+      ~ eof, || (var.0, res, var.1)));
+
+/// Parse with the given parser, and also give the text that was parsed
+fn with_text<'a>(input: &'a [u8], parser: fn(&[u8]) -> IResult<&[u8], Expression>) -> IResult<&'a [u8], (Expression, String)> {
+    match parser(input) {
+        IResult::Done(rest, e) => IResult::Done(rest, (e, String::from_utf8_lossy(&input[..input.len() - rest.len()]).into_owned())),
+        IResult::Error(e) => IResult::Error(e),
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+    }
+}
+
+/// The value being converted, and the text it was written as
+pub fn source(input: &[u8]) -> IResult<&[u8], (Expression, String)> {
+    with_text(input, expr)
+}
+
+/// The target of a unit conversion, and the text it was written as (for display)
+pub fn target(input: &[u8]) -> IResult<&[u8], (Expression, String)> {
+    with_text(input, fac)
+}
+
+/// A unit conversion, e.g. `5 km in m` or `60 mi/hr to m/s`. The expressions are not evaluated.
+named!(pub conversion<((Expression, String), (Expression, String))>, chain!(
+        opt!(multispace)
+      ~ res: source
+      ~ multispace
+      ~ map_opt!(name, |a| if is_keyword(a) { Some(()) } else { None })
+      ~ multispace
+      ~ tgt: target
+      ~ opt!(multispace)
+      ~ eof, || (res, tgt)));
+
+/// Parse postfix (RPN) input, where tokens are separated by whitespace: e.g. `3 4 + 2 *` is
+/// `(3 + 4) * 2`. The expression is not evaluated.
+///
+/// Operators are `+ - * / ^ ±` (or `× ÷ −`), `neg`, and `!`. A function is applied by name
+/// (e.g. `sin`); for a function that takes any number of arguments, or one that is not
+/// builtin, the number of arguments is given after a slash (e.g. `max/3`). Any other token is
+/// an operand, such as a number, unit, or variable. Too few operands for an operation, or more
+/// than one result, is a syntax error.
+///
+/// ```
+/// let expr = ucalc::parse_rpn("3 4 + 2 *").unwrap();
+/// assert_eq!(format!("{}", ucalc::simplify(expr)), "14");
+/// assert!(ucalc::parse_rpn("3 +").is_err());
+/// ```
+pub fn parse_rpn(input: &str) -> Result<Expression, CalculatorError> {
+    let mut stack = Vec::new();
+    for token in input.split_whitespace() {
+        let binary: Option<fn(Box<Expression>, Box<Expression>) -> Expression> = match token {
+            "+" => Some(Expression::Add),
+            "-" | "−" => Some(Expression::Sub),
+            "*" | "×" => Some(Expression::Mul),
+            "/" | "÷" => Some(Expression::Div),
+            "^" | "**" => Some(Expression::Exp),
+            "±" => Some(Expression::PlusMinus),
+            _ => None,
+        };
+        if let Some(op) = binary {
+            let b = try!(stack.pop().ok_or(CalculatorError::SyntaxError));
+            let a = try!(stack.pop().ok_or(CalculatorError::SyntaxError));
+            stack.push(op(Box::new(a), Box::new(b)));
+            continue
+        }
+        if token == "neg" || token == "!" {
+            let a = Box::new(try!(stack.pop().ok_or(CalculatorError::SyntaxError)));
+            stack.push(if token == "neg" { Expression::Neg(a) } else { Expression::Fact(a) });
+            continue
+        }
+        // a function, with the number of arguments given or known
+        let mut parts = token.splitn(2, '/');
+        let call = match (parts.next().unwrap_or(""), parts.next()) {
+            (name, Some(n)) if function_name(name.as_bytes()).is_some() => n.parse().ok().map(|n| (name, n)),
+            (name, None) => get_function(name.as_bytes()).map(|(arity, _)| (name, arity)),
+            _ => None,
+        };
+        match call {
+            Some((_, VARIADIC)) => return Err(CalculatorError::SyntaxError),
+            Some((name, n)) => {
+                if n > stack.len() {
+                    return Err(CalculatorError::SyntaxError)
+                }
+                let args = stack.split_off(stack.len() - n);
+                stack.push(Expression::Call(name.to_owned(), args));
+            },
+            None => match raw_input(token.as_bytes()) {
+                IResult::Done(_, expr) => stack.push(expr),
+                _ => return Err(CalculatorError::SyntaxError),
+            },
+        }
+    }
+    match (stack.pop(), stack.is_empty()) {
+        (Some(expr), true) => Ok(expr),
+        _ => Err(CalculatorError::SyntaxError),
+    }
+}
+
+/// User input, evaluated with the default settings
+named!(pub input<Expression>, map!(raw_input, simplify));
+
+/// Evaluate an expression with the default settings
+pub fn simplify(expr: Expression) -> Expression {
+    calc::Calculator::new().evaluate(expr)
+}
+
+/// Parse and evaluate an expression with the default settings. Malformed input gives a
+/// `SyntaxError` rather than a panic.
+///
+/// ```
+/// let v = ucalc::evaluate("2 ft * 3 m").unwrap();
+/// assert_eq!(format!("{}", v), "1143/625 m^2");
+/// assert_eq!(ucalc::evaluate("2 +"), Err(ucalc::CalculatorError::SyntaxError));
+/// ```
+pub fn evaluate(input: &str) -> Result<uval::UnitValue, CalculatorError> {
+    with_stack(|| match raw_input(input.as_bytes()) {
+        IResult::Done(_, expr) => match simplify(expr) {
+            Expression::Value(val) => Ok(val),
+            // the uncertainty is dropped; use a Calculator to keep it
+            Expression::Measured(m) => Ok(m.value),
+            Expression::Error(e) => Err(CalculatorError::from(e)),
+            // e.g. an undefined variable
+            _ => Err(CalculatorError::SyntaxError),
+        },
+        _ => Err(CalculatorError::SyntaxError),
+    })
+}
+
+// the following tests are self-explanatory.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nom::*;
+    use std;
+    use rational::AsFloat;
+    /// Macro used for testing an expression against a known value
+    macro_rules! test_expr {
+        ( $x:expr, $v: expr) => (assert_eq!(input($x.as_bytes()), IResult::Done(&b""[..], make_value($v))));
+    }
+    /// Macro used for approximately equal
+    macro_rules! test_approx {
+        ( $x:expr, $v: expr) => ({
+            let res = input($x.as_bytes());
+            match &res {
+                &IResult::Done(_, Expression::Value(val)) => {
+                    assert_eq!(res, IResult::Done(&b""[..], Expression::Value(val)));
+                    assert!((val.as_float() - $v).abs() < 1e-6)
+                },
+                _ => panic!("input not consumed: {:?}", res)
+            }});
+    }
+    /// Macro used for testing an expression against a known value, which must also be exact
+    macro_rules! test_exact {
+        ( $x:expr, $v: expr) => ({
+            test_expr!($x, $v);
+            match input($x.as_bytes()) {
+                IResult::Done(_, Expression::Value(val)) => assert!(val.value.get_exact().is_some(), "{} is inexact", $x),
+                res => panic!("input not consumed: {:?}", res)
+            }});
+    }
+    /// Macro used for testing an expression against a known value, which must also be inexact
+    macro_rules! test_inexact {
+        ( $x:expr, $v: expr) => ({
+            test_approx!($x, $v);
+            match input($x.as_bytes()) {
+                IResult::Done(_, Expression::Value(val)) => assert!(val.value.get_exact().is_none(), "{} is exact", $x),
+                res => panic!("input not consumed: {:?}", res)
+            }});
+    }
+    /// An expression should not parse correctly.
+    macro_rules! fail_expr {
+        ( $x: expr ) => (match input($x.as_bytes()) { IResult::Done(_, _) => panic!("should have failed"), _ => () })
+    }
+    #[test]
+    fn test_exponents() {
+        test_expr!("2^1^5", 2.0);
+        test_expr!("2^3^2", 512.0);
+        test_expr!("2 ** 3", 8.0);
+        test_expr!("2**3**2", 512.0);
+        test_expr!("2 ** 3 ^ 2", 512.0);
+        test_expr!("2 ** -1", 0.5);
+        test_expr!("3 * 2 ** 2", 12.0);
+        test_expr!("2 ** 2 * 3", 12.0);
+        fail_expr!("2 * * 3");
+        fail_expr!("2 *** 3");
+        fail_expr!("2 **");
+    }
+
+    #[test]
+    fn test_muldiv() {
+        test_expr!("2*3", 6.0);
+        test_expr!("3/2", 1.5);
+        test_expr!("3/2*4", 6.0);
+        test_expr!("2^2*3", 12.0);
+        test_expr!("2 2 2 ", 8.0);
+    }
+
+    #[test]
+    fn test_implied_mul() {
+        test_expr!("1/2(4)", 0.125);
+        test_expr!("1/2 (4)", 2.0);
+        test_expr!("1(2)3(4)5(6)7(8)9(10)", 3628800.0)
+    }
+
+    #[test]
+    fn test_scientific_exactness() {
+        // integer-valued scientific literals are exact
+        test_exact!("2.3e2", 230.0);
+        test_exact!("5e3", 5000.0);
+        test_exact!("1.2e1", 12.0);
+        test_exact!("1.5E1", 15.0);
+        test_exact!("2.5e-1", 0.25);
+        test_exact!("1.23e0", 1.23);
+        test_exact!("1e-3", 0.001);
+        // other values are inexact
+        test_inexact!("1e-19", 1e-19);
+        test_inexact!("1.23456789e-15", 1.23456789e-15);
+    }
+
+    #[test]
+    fn test_exactness() {
+        test_exact!("1/3", 1.0 / 3.0);
+        test_exact!("2^-2", 0.25);
+        test_exact!("0.125", 0.125);
+        test_exact!("3/2*4", 6.0);
+        test_exact!("2.3e2", 230.0);
+        test_exact!("1_000 - 0.5", 999.5);
+        test_exact!("(4/9)^(1/2)", 2.0 / 3.0);
+        // a difference of zero is always exact
+        test_exact!("1/2 pi - pi/2", 0.0);
+        test_exact!("0.1", 0.1);
+        test_exact!("0.1 + 1/2", 0.6);
+        test_inexact!("0.12345678901234567891", 0.12345678901234567891);
+        test_inexact!("0.12345678901234567891 + 1/2", 0.62345678901234567891);
+        test_inexact!("pi", std::f64::consts::PI);
+        test_inexact!("2^(1/2)", std::f64::consts::SQRT_2);
+        test_inexact!("sin(0)", 0.0);
+        // overflow makes exact values inexact
+        test_inexact!("1/65536/65536/65536/65536", 1.0 / 65536.0 / 65536.0 / 65536.0 / 65536.0);
+        test_inexact!("(3/2)^100", 1.5f64.powi(100));
+    }
+
+    #[test]
+    fn test_wide_exact() {
+        // these overflow a fraction of 32-bit integers, but not one of 64-bit integers
+        test_exact!("1/65536/65536", 1.0 / 65536.0 / 65536.0);
+        test_exact!("65536(65536)", 4294967296.0);
+        test_exact!("1(2)3(4)5(6)7(8)9(10)11(12)13(14)", 87178291200.0);
+        assert_eq!(format!("{}", evaluate("(3/2)^39").unwrap()), "4052555153018976267/549755813888");
+        assert_eq!(format!("{}", evaluate("1 mi * 1 mi * 1 mi").unwrap()), "8140980127813632/1953125 m^3");
+    }
+
+    #[test]
+    fn test_negative_roots() {
+        test_expr!("(-8)^(1/3)", -2.0);
+        test_expr!("(-8)^(2/3)", 4.0);
+        test_expr!("-8^(1/3)", -2.0);
+        test_expr!("(-32)^(3/5)", -8.0);
+        assert_eq!(input(b"(-4)^(1/2)"), IResult::Done(&b""[..], Expression::Error(value::ArithmeticError::DomainError)));
+        assert_eq!(input(b"(-8)^(1/6)"), IResult::Done(&b""[..], Expression::Error(value::ArithmeticError::DomainError)));
+    }
+
+    #[test]
+    fn test_addsub() {
+        test_expr!("1+1", 2.0);
+        test_expr!("3-2", 1.0);
+        test_expr!("3-2+3", 4.0);
+        test_expr!("2^3*4-5", 27.0);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        test_expr!(" (2^39)* 122/2 + 80 -1023 ", 33535104646225.0);
+        test_expr!("(    2     ^   1   )   * 5    / 2 +   3    - 5", 3.0);
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        test_expr!("2 ", 2.0);
+        test_expr!("2 + 3 ", 5.0);
+        test_expr!("2 3 ", 6.0);
+        test_expr!("2 3\t \n", 6.0);
+        test_expr!("2 - 3 \n", -1.0);
+        test_expr!("(2) ", 2.0);
+        fail_expr!("2 + ");
+        fail_expr!("2 * ");
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        // '?' is an ordinary character, not an end marker
+        fail_expr!("2?");
+        fail_expr!("2?3");
+        fail_expr!("2 ? 3");
+        fail_expr!("?");
+        // numbers and names at the very end of the input
+        test_expr!("2.", 2.0);
+        test_expr!("2e3", 2000.0);
+        test_approx!("2 pi", 2.0 * std::f64::consts::PI);
+    }
+
+    #[test]
+    fn test_huge() {
+        test_expr!("(((17 - 9 - 14) / 1 + 13 * 15) / 5 / 8 - 18) / 11 * 15 * 17 / (16 / 5 + 10 * 16 / ((5 / 14 - 3 - 4 - 6) * (9 * 7 / 2 - 7 - 16)))", -179.844926355302559466636533137465393525057912876433696);
+    }
+
+    #[test]
+    fn test_unary() {
+        test_expr!("1+-1(2)", -1.0);
+        test_expr!("1/2-2", -1.5);
+        test_expr!("1+1", 2.0);
+        test_expr!("1 + 1", 2.0);
+        test_expr!("1+1/-(3-2)", 0.0);
+        test_expr!("-2^2", -4.0);
+        test_expr!("2^-2", 0.25);
+        test_expr!("-2(5)", -10.0);
+    }
+
+    #[test]
+    fn test_thomas() {
+        test_expr!("1+1", 2.0);
+        test_expr!("2^(3*2-4)-4", 0.0);
+    }
+
+    #[test]
+    fn test_floating() {
+        test_expr!("5", 5.0);
+        test_expr!("2.3e2", 230.0);
+        test_expr!("5e-2", 0.05);
+        test_expr!("8_230_999", 8_230_999.0);
+        fail_expr!("_");
+        test_expr!(".2", 0.2);
+        // Rust reference examples
+        test_expr!("123.0", 123.0f64);
+        test_expr!("0.1", 0.1f64);
+        test_expr!("12E+99", 12E+99_f64);
+        test_expr!("2.", 2.);
+    }
+
+    #[test]
+    fn test_num_const() {
+        test_expr!("pi", std::f64::consts::PI);
+        test_expr!("e", std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_function() {
+        test_approx!("sin(pi/6)", 0.5);
+        test_approx!("atan2(1, 1)", std::f64::consts::FRAC_PI_4);
+    }
+
+    #[test]
+    fn test_root() {
+        test_exact!("root(3, 27)", 3.0);
+        test_exact!("root(2, 16)", 4.0);
+        test_exact!("root(3, -8)", -2.0);
+        test_exact!("root(2, 9/4)", 1.5);
+        test_inexact!("root(2, 2)", std::f64::consts::SQRT_2);
+        test_exact!("root(0.5, 3)", 9.0);
+        assert_eq!(evaluate("root(2, -4)"), Err(CalculatorError::DomainError));
+        assert_eq!(evaluate("root(0, 4)"), Err(CalculatorError::DomainError));
+        assert_eq!(format!("{}", evaluate("root(2, 16 m^2)").unwrap()), "4 m");
+        assert_eq!(evaluate("root(2 m, 16)"), Err(CalculatorError::UnitError));
+    }
+
+    #[test]
+    fn test_mixed_units() {
+        // values are stored in SI base units
+        let m = units::get("m").unwrap();
+        let ft = units::get("ft").unwrap();
+        let two = uval::UnitValue::from_input(2.0).unwrap();
+        let three = uval::UnitValue::from_input(3.0).unwrap();
+        test_expr!("2 ft * 3 m", two * ft * three * m);
+        test_expr!("2 ft * 3 m", three * m * two * ft);
+        test_expr!("6 ft / (2 m)", uval::UnitValue::from_input(3.0).unwrap() * ft / m);
+        match input(b"2 ft * 3 m") {
+            IResult::Done(_, val) => assert_eq!(format!("{}", val), "1143/625 m^2"),
+            res => panic!("input not consumed: {:?}", res),
+        }
+        match input(b"6 ft / (2 m)") {
+            IResult::Done(_, val) => assert_eq!(format!("{}", val), "1143/1250"),
+            res => panic!("input not consumed: {:?}", res),
+        }
+        // but a calculator shows results in the unit system of the left operand
+        let mut calc = calc::Calculator::new();
+        let mut show = |line: &str| {
+            let val = calc.run(line).unwrap();
+            calc.format(line, &val)
+        };
+        assert_eq!(show("2 ft * 3 m"), "2500/127 ft^2");
+        assert_eq!(show("3 m * 2 ft"), "1143/625 m^2");
+        assert_eq!(show("(2 ft + 3 m) * 2"), "3008/127 ft");
+        assert_eq!(show("1 mi / (2 min)"), "44 ft/s");
+        assert_eq!(show("2 km / 1 mi"), "15625/12573");
+        assert_eq!(show("6 ft / (2 m)"), "1143/1250");
+        assert_eq!(show("2 ft * 3 ft"), "6 ft^2");
+        // dimensions other than length and mass keep their SI units
+        assert_eq!(show("2 ft * 3 A"), "6 ft*A");
+        assert_eq!(show("3 ft in m"), "1143/1250 m");
+    }
+
+    #[test]
+    fn test_percent() {
+        test_exact!("50%", 0.5);
+        assert_eq!(format!("{}", evaluate("50%").unwrap()), "1/2");
+        test_exact!("200% * 3", 6.0);
+        test_exact!("200%*3", 6.0);
+        test_exact!("3 * 200%", 6.0);
+        test_exact!("12.5% + 1", 1.125);
+        test_exact!("(20 + 30)%", 0.5);
+        test_exact!("50%%", 0.005);
+        test_exact!("3!%", 0.06);
+        test_exact!("-50%", -0.5);
+        test_exact!("percent(25)", 0.25);
+        test_inexact!("pi%", std::f64::consts::PI / 100.0);
+        test_expr!("(2 m)%", Err(value::ArithmeticError::UnitError));
+        // followed by an operand, % is not a percentage (it is reserved for modulo)
+        for bad in &["10 % 3", "10%3", "50% m", "50 %", "%"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_of() {
+        test_exact!("20% of 50", 10.0);
+        test_exact!("25% of 80", 20.0);
+        assert_eq!(format!("{}", evaluate("20% of 50 m").unwrap()), "10 m");
+        // looser than multiplication, tighter than addition
+        test_exact!("1 + 50% of 2 * 3", 4.0);
+        test_exact!("10% of 200 - 5", 15.0);
+        test_exact!("1/2 of 10", 5.0);
+        test_exact!("50% of 50% of 8", 2.0);
+        test_expr!("2 m of 3", Err(value::ArithmeticError::UnitError));
+        for bad in &["20% of", "of 50", "20%of 50", "20% of50"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_dms() {
+        // in radians, with the default settings
+        test_approx!("40d30m", 40.5f64.to_radians());
+        test_approx!("40d30m15s", (40.0f64 + 30.0 / 60.0 + 15.0 / 3600.0).to_radians());
+        test_approx!("40°30'15\"", (40.0f64 + 30.0 / 60.0 + 15.0 / 3600.0).to_radians());
+        test_approx!("2 * 10d30m", 21f64.to_radians());
+        test_approx!("dms(40, 30, 0)", 40.5f64.to_radians());
+        // d alone is not an angle, and other names are not minutes or seconds
+        assert_eq!(evaluate("40d"), Err(CalculatorError::SyntaxError));
+        assert_eq!(evaluate("40d30min"), Err(CalculatorError::SyntaxError));
+        assert_eq!(evaluate("40d30m15"), Err(CalculatorError::SyntaxError));
+        test_expr!("dms(1 m, 0, 0)", Err(value::ArithmeticError::UnitError));
+    }
+
+    #[test]
+    fn test_to_rpn() {
+        /// The RPN tokens of an expression, separated by spaces
+        fn rpn(input: &str) -> String {
+            match raw_input(input.as_bytes()) {
+                IResult::Done(_, expr) => expr.to_rpn().iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" "),
+                res => panic!("input not consumed: {:?}", res),
+            }
+        }
+        assert_eq!(rpn("2 + 3 * 4"), "2 3 4 * +");
+        assert_eq!(rpn("(2 + 3) * 4"), "2 3 + 4 *");
+        assert_eq!(rpn("1 - 2 - 3"), "1 2 - 3 -");
+        assert_eq!(rpn("2^3^2"), "2 3 2 ^ ^");
+        assert_eq!(rpn("-x! + 10 ± 0.5"), "x ! neg 10 + 0.5 ±");
+        // units are written as they were typed, but constants are values once parsed
+        assert_eq!(rpn("atan2(1, 2 m) + sin(pi)"), "1 2 m * atan2/2 3.141592653589793 sin/1 +");
+        match raw_input(b"2 + 3 * 4") {
+            IResult::Done(_, expr) => assert_eq!(expr.to_rpn(), vec![
+                RpnToken::Operand(Expression::Number("2".to_owned())),
+                RpnToken::Operand(Expression::Number("3".to_owned())),
+                RpnToken::Operand(Expression::Number("4".to_owned())),
+                RpnToken::Operator('*'),
+                RpnToken::Operator('+'),
+            ]),
+            res => panic!("input not consumed: {:?}", res),
+        }
+        // an evaluated expression is a single operand
+        match input(b"2 + 3 * 4") {
+            IResult::Done(_, expr) => assert_eq!(expr.to_rpn(), vec![RpnToken::Operand(expr)]),
+            res => panic!("input not consumed: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_parse_rpn() {
+        /// Evaluate postfix input
+        fn rpn(input: &str) -> Result<uval::UnitValue, CalculatorError> {
+            match simplify(try!(parse_rpn(input))) {
+                Expression::Value(val) => Ok(val),
+                Expression::Error(e) => Err(CalculatorError::from(e)),
+                _ => Err(CalculatorError::SyntaxError),
+            }
+        }
+        let n = |a| Ok(uval::UnitValue::from_input(a).unwrap());
+        assert_eq!(rpn("3 4 +"), n(7.0));
+        assert_eq!(rpn("3 4 + 2 *"), n(14.0));
+        assert_eq!(rpn("  10 4 - 2 /  "), n(3.0));
+        assert_eq!(rpn("2 3 ^ neg 3 ! +"), n(-2.0));
+        assert_eq!(rpn("12 18 gcd 2 /"), n(3.0));
+        assert_eq!(rpn("3 7 2 max/3 1/2 *"), n(3.5));
+        assert_eq!(rpn("5 3 −"), n(2.0));
+        assert_eq!(format!("{}", rpn("2 m * 3 s * /").unwrap()), "2/3 m/s");
+        assert_eq!(rpn("1 0 /"), Err(CalculatorError::DivideByZeroError));
+        // too few operands, too many, or nothing at all
+        for bad in &["3 +", "+", "neg", "1 2", "max", "atan2/2", "", "1 2 (", "x/y"] {
+            assert_eq!(rpn(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+        // the round trip through to_rpn gives the same expression
+        match raw_input(b"-(2 + 3) * 4! - min(1, x, 2) / gcd(4, 6)") {
+            IResult::Done(_, expr) => {
+                let tokens = expr.to_rpn().iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" ");
+                assert_eq!(parse_rpn(&tokens), Ok(expr));
+            },
+            res => panic!("input not consumed: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_count_ops() {
+        match raw_input(b"2 + 3 * 4 - -sin(pi/2)^2 (5) + atan2(1, 2 2)") {
+            IResult::Done(_, expr) => {
+                let counts = expr.count_ops();
+                assert_eq!(counts, OpCounts { exp: 1, mul: 3, div: 1, add: 2, sub: 1, neg: 1, fact: 0, plus_minus: 0, call: 2 });
+                assert_eq!(counts.total(), 11);
+            },
+            res => panic!("input not consumed: {:?}", res),
+        }
+        // evaluated expressions have no operations left
+        match input(b"2 + 3 * 4") {
+            IResult::Done(_, expr) => assert_eq!(expr.count_ops(), OpCounts::default()),
+            res => panic!("input not consumed: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_adjacent_units() {
+        // a unit directly after a number is implied multiplication
+        let m = units::get("m").unwrap();
+        test_expr!("5m", m * uval::UnitValue::from_input(5.0).unwrap());
+        test_expr!("5 m", m * uval::UnitValue::from_input(5.0).unwrap());
+        test_expr!("5km", m * uval::UnitValue::from_input(5000.0).unwrap());
+        test_expr!("2.5kg", units::get("kg").unwrap() * uval::UnitValue::from_input(2.5).unwrap());
+        // an "e" that does not start an exponent is not part of the number
+        test_approx!("2e", 2.0 * std::f64::consts::E);
+        test_expr!("2e1m", m * uval::UnitValue::from_input(20.0).unwrap());
+    }
+
+    #[test]
+    fn test_exact_functions() {
+        test_exact!("abs(-3/2)", 1.5);
+        test_exact!("abs(3/2)", 1.5);
+        test_exact!("sign(-3/2)", -1.0);
+        test_exact!("sign(0)", 0.0);
+        test_exact!("sign(pi)", 1.0);
+        test_exact!("floor(3/2)", 1.0);
+        test_exact!("floor(-3/2)", -2.0);
+        test_exact!("ceil(3/2)", 2.0);
+        test_exact!("ceil(-3/2)", -1.0);
+        // halfway cases round to even by default
+        test_exact!("round(5/2)", 2.0);
+        test_exact!("round(-5/2)", -2.0);
+        test_exact!("round(7/2)", 4.0);
+        test_exact!("round(7/3)", 2.0);
+        test_inexact!("round(2.5 + 0 pi)", 2.0);
+        test_exact!("trunc(-7/2)", -3.0);
+        test_exact!("factorial(5)", 120.0);
+        test_exact!("factorial(0)", 1.0);
+        test_exact!("gcd(12, -18)", 6.0);
+        test_exact!("floor(abs(-7/2)) + 1/2", 3.5);
+        // inexact arguments stay inexact
+        test_inexact!("floor(pi)", 3.0);
+        test_inexact!("factorial(21)", 51090942171709440000.0);
+        test_expr!("abs(-2 m)", units::get("m").unwrap() * uval::UnitValue::from_input(2.0).unwrap());
+        test_expr!("floor(2 m)", Err(value::ArithmeticError::UnitError));
+        test_expr!("factorial(-1)", Err(value::ArithmeticError::DomainError));
+        test_expr!("factorial(1/2)", Err(value::ArithmeticError::DomainError));
+        test_expr!("gcd(1/2, 2)", Err(value::ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_rounding_functions() {
+        test_exact!("floor(7/2)", 3.0);
+        test_exact!("round(2.5)", 2.0);
+        test_exact!("abs(-5 m) / m", 5.0);
+        // the sign is unitless
+        test_exact!("sign(-5 m)", -1.0);
+    }
+
+    #[test]
+    fn test_lerp() {
+        test_expr!("lerp(0 m, 10 m, 1/2)", units::get("m").unwrap() * uval::UnitValue::from_input(5.0).unwrap());
+        test_exact!("lerp(2, 4, 0.25)", 2.5);
+        test_exact!("lerp(2, 4, 3/2)", 5.0);
+        test_inexact!("lerp(0, 1, pi)", std::f64::consts::PI);
+        test_expr!("lerp(0 m, 10 s, 0.5)", Err(value::ArithmeticError::UnitError));
+        test_expr!("lerp(0 m, 10 m, 1 s)", Err(value::ArithmeticError::UnitError));
+    }
+
+    #[test]
+    fn test_min_max() {
+        test_exact!("max(3, 7, 2)", 7.0);
+        test_exact!("min(3, 7, 2)", 2.0);
+        test_exact!("max(-1/2)", -0.5);
+        test_exact!("min(1/3, pi, 2)", 1.0 / 3.0);
+        test_inexact!("max(1/3, pi, 2)", std::f64::consts::PI);
+        test_expr!("max(1 m, 3 ft) / m", uval::UnitValue::from_input(1.0).unwrap());
+        test_expr!("max(1 m, 2 s)", Err(value::ArithmeticError::UnitError));
+    }
+
+    #[test]
+    fn test_combinatorics() {
+        test_exact!("nCr(5, 2)", 10.0);
+        test_exact!("nPr(5, 2)", 20.0);
+        test_exact!("nCr(52, 5)", 2598960.0);
+        test_exact!("nPr(3, 4)", 0.0);
+        test_exact!("nCr(40, 20)", 137846528820.0);
+        test_expr!("nCr(68, 34)", Err(value::ArithmeticError::OverflowError));
+        test_expr!("nPr(21, 21)", Err(value::ArithmeticError::OverflowError));
+        test_expr!("nCr(5, -1)", Err(value::ArithmeticError::DomainError));
+        test_expr!("nPr(5/2, 1)", Err(value::ArithmeticError::DomainError));
+        test_expr!("nCr(5 m, 2)", Err(value::ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_factor() {
+        // the number of distinct prime factors
+        test_exact!("factor(360)", 3.0);
+        test_exact!("factor(97)", 1.0);
+        test_exact!("factor(1)", 0.0);
+        test_exact!("factor(2^10 * 3)", 2.0);
+        test_expr!("factor(0)", Err(value::ArithmeticError::DomainError));
+        test_expr!("factor(-4)", Err(value::ArithmeticError::DomainError));
+        test_expr!("factor(2.5)", Err(value::ArithmeticError::DomainError));
+        test_expr!("factor(pi / pi)", Err(value::ArithmeticError::DomainError));
+        test_expr!("factor(4 m)", Err(value::ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_isprime() {
+        test_exact!("isprime(2)", 1.0);
+        test_exact!("isprime(97)", 1.0);
+        test_exact!("isprime(2147483647)", 1.0);
+        test_exact!("isprime(65536*65536-5)", 1.0);
+        test_exact!("isprime(1)", 0.0);
+        test_exact!("isprime(91)", 0.0);
+        test_exact!("isprime(360)", 0.0);
+        test_expr!("isprime(0)", Err(value::ArithmeticError::DomainError));
+        test_expr!("isprime(-7)", Err(value::ArithmeticError::DomainError));
+        test_expr!("isprime(7/2)", Err(value::ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_conversion_keywords() {
+        let inch = units::get("in").unwrap();
+        // "in" is still a unit when it's not followed by a conversion target
+        test_expr!("5 in", inch * uval::UnitValue::from_input(5.0).unwrap());
+        test_expr!("3 in + 2 in", inch * uval::UnitValue::from_input(5.0).unwrap());
+        test_expr!("5in", inch * uval::UnitValue::from_input(5.0).unwrap());
+        // but a conversion is not part of an expression
+        fail_expr!("5 km in m");
+        fail_expr!("5 km to m");
+        match conversion(b"5 km in m / s") {
+            IResult::Done(_, ((_, ref source), (_, ref text))) => {
+                assert_eq!(source, "5 km");
+                assert_eq!(text, "m / s");
+            },
+            res => panic!("conversion not parsed: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let m = units::get("m").unwrap();
+        assert!(units::get("km").unwrap().value.get_exact().is_some());
+        test_expr!("2 km + 500 m", m * uval::UnitValue::from_input(2500.0).unwrap());
+        test_expr!("mm", m / uval::UnitValue::from_input(1000.0).unwrap());
+        test_expr!("3 µm", units::get("um").unwrap() * uval::UnitValue::from_input(3.0).unwrap());
+        test_expr!("GW / kW", 1000000.0);
+        test_expr!("dam", m * uval::UnitValue::from_input(10.0).unwrap());
+        // a registered unit is preferred over a prefixed one
+        test_expr!("min", units::get("s").unwrap() * uval::UnitValue::from_input(60.0).unwrap());
+        // constants can't be prefixed
+        assert!(units::get("k_c").is_none());
+        assert!(units::get("k").is_none());
+    }
+
+    #[test]
+    fn test_factorial_operator() {
+        test_exact!("5!", 120.0);
+        test_exact!("0!", 1.0);
+        test_exact!("3!!", 720.0);
+        test_exact!("(1 + 2)!", 6.0);
+        // ! binds more tightly than exponentiation, negation, and implied multiplication
+        test_exact!("2^3!", 64.0);
+        test_exact!("3!^2", 36.0);
+        test_exact!("-3!", -6.0);
+        test_exact!("2 3!", 12.0);
+        test_exact!("20!", 2432902008176640000.0);
+        test_inexact!("21!", 51090942171709440000.0);
+        test_expr!("2.5!", Err(value::ArithmeticError::DomainError));
+        test_expr!("(-1)!", Err(value::ArithmeticError::DomainError));
+        assert_eq!(evaluate("(3 m)!"), Err(CalculatorError::UnitError));
+        assert_eq!(evaluate("factorial(3 m)"), Err(CalculatorError::UnitError));
+        fail_expr!("!");
+        fail_expr!("5 !");
+    }
+
+    #[test]
+    fn test_floor_brackets() {
+        test_exact!("⌊3.7⌋", 3.0);
+        test_exact!("⌈3.2⌉", 4.0);
+        test_exact!("⌊-7/2⌋", -4.0);
+        test_exact!("⌈ -7/2 ⌉", -3.0);
+        test_exact!("2⌊5/2⌋ + 1", 5.0);
+        test_exact!("⌈⌊5/2⌋ / 3⌉", 1.0);
+        test_inexact!("⌊pi⌋", 3.0);
+        assert_eq!(evaluate("⌊3 m⌋"), Err(CalculatorError::UnitError));
+        fail_expr!("⌊3.7⌉");
+        fail_expr!("⌊3.7");
+    }
+
+    #[test]
+    fn test_long_input() {
+        // the time to parse and evaluate grows linearly with the length of the input: 50,000
+        // characters take about ten times as long as 5,000 (a quadratic parser would take a
+        // hundred times as long)
+        let time = |n: usize| {
+            let start = std::time::Instant::now();
+            let sum: String = (0..n / 2).map(|_| "1+").collect::<String>() + "1";
+            assert_eq!(evaluate(&sum), Ok(uval::UnitValue::from_input((n / 2 + 1) as f64).unwrap()));
+            let product: String = (0..n / 2 - 1).map(|_| "1 ").collect::<String>() + "2";
+            assert_eq!(evaluate(&product), Ok(uval::UnitValue::from_input(2.0).unwrap()));
+            let number: String = (0..n).map(|_| "1").collect();
+            assert_eq!(evaluate(&number), Err(CalculatorError::OverflowError));
+            start.elapsed()
+        };
+        let (short, long) = (time(5000), time(50000));
+        assert!(long < short * 30, "5,000 characters took {:?}, but 50,000 took {:?}", short, long);
+    }
+
+    #[test]
+    fn test_pathological_input() {
+        // every repeated rule consumes input, so long runs of one character end
+        for c in &["!", " ", "*", "-", "+", "_", "|", "e", "^", "×", ":", "=", "(", ")"] {
+            let run: String = (0..5000).map(|_| *c).collect();
+            evaluate(&run).ok();
+            evaluate(&format!("2{}", run)).ok();
+            evaluate(&format!("2{}3", run)).ok();
+        }
+        // deep nesting is a syntax error rather than a stack overflow (the parser is run
+        // directly here, so it needs as much stack as a line would get)
+        with_stack(|| {
+            let nested = |n| (0..n).map(|_| "(").collect::<String>() + "1" + &(0..n).map(|_| ")").collect::<String>();
+            test_exact!(&nested(MAX_DEPTH - 1), 1.0);
+            assert_eq!(evaluate(&nested(MAX_DEPTH + 1)), Err(CalculatorError::SyntaxError));
+            let calls = |n| (0..n).map(|_| "abs(").collect::<String>() + "1" + &(0..n).map(|_| ")").collect::<String>();
+            test_exact!(&calls(MAX_DEPTH - 1), 1.0);
+            assert_eq!(evaluate(&calls(MAX_DEPTH + 1)), Err(CalculatorError::SyntaxError));
+            // but runs of signs and postfix operators are read in a loop, so they aren't limited
+            let signs: String = (0..10000).map(|_| "-").collect::<String>() + "1";
+            test_exact!(&signs, 1.0);
+            test_exact!(&signs[1..], -1.0);
+            let bangs: String = (0..10000).map(|_| "!").collect();
+            assert_eq!(evaluate(&format!("1{}", bangs)), Ok(uval::UnitValue::from_input(1.0).unwrap()));
+            let percents: String = (0..10000).map(|_| "%").collect();
+            assert_eq!(evaluate(&format!("0{}", percents)), Ok(uval::UnitValue::from_input(0.0).unwrap()));
+        });
+    }
+
+    #[test]
+    fn test_parser_progress() {
+        // A parser that succeeds without consuming anything would make any repetition of it
+        // loop forever, so every parser that can be repeated must consume input or fail.
+        macro_rules! assert_progress {
+            ($parser: expr, $input: expr) => (match $parser($input.as_bytes()) {
+                IResult::Done(rest, _) => assert!(rest.len() < $input.len(), "{} did not consume {:?}", stringify!($parser), $input),
+                _ => (),
+            })
+        }
+        for start in &["", "2", "m", "(1)", "2 m"] {
+            for end in &["", " ", "!", "%", "*", "/", "+", "-", "^", "_", "×", ")", " of", " in", " m"] {
+                let input = format!("{}{}", start, end);
+                if input.is_empty() {
+                    continue
+                }
+                assert_progress!(expr, input);
+                assert_progress!(portion, input);
+                assert_progress!(fac, input);
+                assert_progress!(unary, input);
+                assert_progress!(imul, input);
+                assert_progress!(postfix, input);
+                assert_progress!(atom, input);
+                assert_progress!(unit_expr, input);
+                assert_progress!(name, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_negative_literals() {
+        test_exact!("-0.5", -0.5);
+        assert_eq!(format!("{}", evaluate("-0.5").unwrap()), "-1/2");
+        test_exact!("-0.125", -0.125);
+        test_exact!("-0.1", -0.1);
+        test_exact!("-2.5e-3", -0.0025);
+        test_exact!("--0.5", 0.5);
+        test_inexact!("-0.12345678901234567891", -0.12345678901234567891);
+        test_inexact!("-9223372036854775808.5", -9223372036854775808.5);
+        // a negative literal is exact exactly when the positive one is
+        for a in &["0.5", "0.125", "0.1", "0.375", "0.12345678901234567891", "268435455.875", "2147483648",
+                   "9223372036854775808", "1e-10", "1e-19"] {
+            let pos = evaluate(a).unwrap();
+            let neg = evaluate(&format!("-{}", a)).unwrap();
+            assert_eq!(neg, -pos, "-{}", a);
+            assert_eq!(neg.value.get_exact().is_some(), pos.value.get_exact().is_some(), "-{}", a);
+        }
+    }
+
+    #[test]
+    fn test_derived_units() {
+        assert_eq!(evaluate("1 N * 1 m"), evaluate("1 J"));
+        assert_eq!(evaluate("5 N * 2 m"), evaluate("10 J"));
+        assert_eq!(evaluate("1 N"), evaluate("1 kg m / s^2"));
+        assert_eq!(evaluate("1 J / (1 s)"), evaluate("1 W"));
+        assert_eq!(evaluate("1 N / (1 m^2)"), evaluate("1 Pa"));
+        assert_eq!(evaluate("1 Hz"), evaluate("1 / s"));
+        assert_eq!(evaluate("1 N * 1 m").map(|a| a.unit), evaluate("1 J").map(|a| a.unit));
+        for &(input, shown) in &[("3 N", "3 N"), ("3 J", "3 J"), ("3 W", "3 W"), ("3 Pa", "3 Pa"), ("3 Hz", "3 Hz")] {
+            assert_eq!(format!("{}", evaluate(input).unwrap()), shown);
+        }
+    }
+
+    #[test]
+    fn test_rational_literal() {
+        test_exact!("3:4", 0.75);
+        test_exact!("6:8 + 1:4", 1.0);
+        test_exact!("-1_000:3", -1000.0 / 3.0);
+        test_exact!("2 3:4", 1.5);
+        assert_eq!(format!("{}", evaluate("3:4 m").unwrap()), "3/4 m");
+        assert_eq!(evaluate("3:0"), Err(CalculatorError::DivideByZeroError));
+        assert_eq!(evaluate("1:99999999999"), Err(CalculatorError::OverflowError));
+        for bad in &["3:", ":4", "3.5:4", "3 : 4"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+        // exact even when division is truncated
+        let mut calc = calc::Calculator::new();
+        calc.mode = calc::CalcMode::Integer;
+        assert_eq!(calc.run("3/4"), Ok(uval::UnitValue::from_input(0.0).unwrap()));
+        assert_eq!(calc.run("3:4"), Ok(uval::UnitValue::from_input(0.75).unwrap()));
+    }
+
+    #[test]
+    fn test_abs_bars() {
+        test_exact!("|-5|", 5.0);
+        test_exact!("|3 - 7|", 4.0);
+        test_exact!("| -7/2 |", 3.5);
+        test_exact!("2|-3| + 1", 7.0);
+        test_inexact!("|-pi|", std::f64::consts::PI);
+        assert_eq!(format!("{}", evaluate("|2 m - 5 m|").unwrap()), "3 m");
+        for bad in &["|", "||", "|-5", "-5|", "|2 +|"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_unicode_operators() {
+        test_exact!("6 × 7 ÷ 2", 21.0);
+        test_exact!("6×7÷2", 21.0);
+        test_exact!("5 − 3", 2.0);
+        test_exact!("5−3", 2.0);
+        test_exact!("−5", -5.0);
+        test_exact!("2 × −3", -6.0);
+        test_exact!("5 − −3", 8.0);
+        assert_eq!(format!("{}", evaluate("6 m × 2 m").unwrap()), "12 m^2");
+        for bad in &["2 ×", "÷ 2", "5 − ", "2 × × 3"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+        assert_eq!(error_position("2 × "), Some(4));
+    }
+
+    #[test]
+    fn test_unary_functions() {
+        test_inexact!("sqrt(16)", 4.0);
+        test_approx!("sqrt(2)", std::f64::consts::SQRT_2);
+        test_approx!("cbrt(-27)", -3.0);
+        test_approx!("ln(e)", 1.0);
+        test_approx!("log10(1000)", 3.0);
+        test_approx!("log2(1/8)", -3.0);
+        test_approx!("exp(2)", std::f64::consts::E * std::f64::consts::E);
+        test_approx!("exp(ln(5))", 5.0);
+        // results that are not real numbers are errors, not NaN
+        assert_eq!(evaluate("sqrt(-1)"), Err(CalculatorError::DomainError));
+        assert_eq!(evaluate("ln(-1)"), Err(CalculatorError::DomainError));
+        for bad in &["ln(0)", "log10(0)", "log2(0)", "log10(-1)", "log2(-8)"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::DomainError), "{}", bad);
+        }
+        // roots take the root of the unit, and the others need plain numbers
+        assert_eq!(format!("{}", evaluate("sqrt(4 m^2)").unwrap()), "2 m");
+        assert_eq!(format!("{}", evaluate("cbrt(27 m^3)").unwrap()), "3 m");
+        assert_eq!(evaluate("sqrt(4 m^2)").unwrap().inexact_reason(), Some(value::InexactReason::IrrationalFunction));
+        assert_eq!(evaluate("sqrt(-4 m^2)"), Err(CalculatorError::DomainError));
+        for bad in &["ln(2 m)", "log10(1 km)", "log2(8 s)", "exp(1 s)"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::UnitError), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_inverse_trig() {
+        use std::f64::consts::PI;
+        test_approx!("asin(1)", PI / 2.0);
+        test_approx!("asin(-1/2)", -PI / 6.0);
+        test_approx!("acos(0)", PI / 2.0);
+        test_approx!("acos(-1)", PI);
+        test_approx!("atan(1)", PI / 4.0);
+        test_approx!("atan(10^10)", PI / 2.0);
+        test_approx!("sin(asin(0.3))", 0.3);
+        // outside [-1, 1] is an error, not NaN
+        assert_eq!(evaluate("asin(2)"), Err(CalculatorError::DomainError));
+        assert_eq!(evaluate("acos(-1.5)"), Err(CalculatorError::DomainError));
+    }
+
+    #[test]
+    fn test_hyperbolic() {
+        test_approx!("sinh(0)", 0.0);
+        test_approx!("cosh(0)", 1.0);
+        test_approx!("tanh(0)", 0.0);
+        test_approx!("sinh(1)", (std::f64::consts::E - 1.0 / std::f64::consts::E) / 2.0);
+        test_approx!("asinh(sinh(2))", 2.0);
+        test_approx!("acosh(1)", 0.0);
+        test_approx!("acosh(cosh(3))", 3.0);
+        test_approx!("atanh(tanh(1/2))", 0.5);
+        assert_eq!(evaluate("acosh(1/2)"), Err(CalculatorError::DomainError));
+        assert_eq!(evaluate("atanh(1)"), Err(CalculatorError::DomainError));
+        assert_eq!(evaluate("atanh(-2)"), Err(CalculatorError::DomainError));
+    }
+
+    #[test]
+    fn test_log() {
+        test_approx!("log(8, 2)", 3.0);
+        test_approx!("log(1/9, 3)", -2.0);
+        test_approx!("log(1, 10)", 0.0);
+        for bad in &["log(-1, 2)", "log(0, 2)", "log(8, 0)", "log(8, -2)", "log(8, 1)"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::DomainError), "{}", bad);
+        }
+        assert_eq!(evaluate("log(8 m, 2)"), Err(CalculatorError::UnitError));
+        assert_eq!(evaluate("log(8, 2 m)"), Err(CalculatorError::UnitError));
+    }
+
+    #[test]
+    fn test_function_arity() {
+        // the wrong number of arguments is an error, not a panic
+        for bad in &["atan2(1)", "sin(1, 2)", "gcd(4)", "log(8)", "abs(1, 2, 3)"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{}", bad);
+        }
+        test_exact!("gcd(4, 6)", 2.0);
+    }
+
+    #[test]
+    fn test_angle_conversion() {
+        test_approx!("sin(deg(30))", 0.5);
+        test_approx!("sin(deg(90))", 1.0);
+        test_inexact!("deg(180)", std::f64::consts::PI);
+        test_approx!("rad(pi)", 180.0);
+        test_approx!("rad(deg(45))", 45.0);
+        // the argument must be a plain number (deg and rad are dimensionless, so angles are too)
+        test_expr!("deg(2 m)", Err(value::ArithmeticError::UnitError));
+        test_expr!("rad(1 s)", Err(value::ArithmeticError::UnitError));
+        test_approx!("rad(90 deg)", 90.0);
+        // without parentheses, they are still units
+        test_approx!("90 deg", std::f64::consts::FRAC_PI_2);
+    }
+
+    #[test]
+    fn test_function_names() {
+        // a function name must be immediately followed by parentheses
+        test_approx!("sin(pi/2)", 1.0);
+        test_approx!("2sin(pi/6)", 1.0);
+        fail_expr!("sin");
+        fail_expr!("sin pi");
+        fail_expr!("sin (pi)");
+        fail_expr!("atan2 (1, 1)");
+        // the whole name is used; a function name followed by more characters is not a call
+        // (it is an undefined variable)
+        assert_eq!(calc::Calculator::new().run("sin_(pi)"), Err(CalculatorError::SyntaxError));
+        assert_eq!(calc::Calculator::new().run("sin2(pi)"), Err(CalculatorError::SyntaxError));
+        // a unit followed by parentheses is implied multiplication
+        test_expr!("m(2)", units::get("m").unwrap() * uval::UnitValue::from_input(2.0).unwrap());
+        test_expr!("m (2)", units::get("m").unwrap() * uval::UnitValue::from_input(2.0).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("1 + 1"), Ok(uval::UnitValue::from_input(2.0).unwrap()));
+        assert_eq!(format!("{}", evaluate("2 m / (4 s)").unwrap()), "1/2 m/s");
+        assert_eq!(evaluate("1/0"), Err(CalculatorError::DivideByZeroError));
+        assert_eq!(evaluate("1 m + 1 s"), Err(CalculatorError::UnitError));
+        for bad in &["", "2 +", "(1", "?", "x := 2", "undefined", "\u{0}", "5 km to"] {
+            assert_eq!(evaluate(bad), Err(CalculatorError::SyntaxError), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_exact_decimals() {
+        assert_eq!(evaluate("0.1 + 0.2"), evaluate("3/10"));
+        assert_eq!(format!("{}", evaluate("0.1 + 0.2").unwrap()), "3/10");
+        assert_eq!(evaluate("0.1 + 0.2 - 0.3"), Ok(uval::UnitValue::zero()));
+        assert_eq!(format!("{}", evaluate("3.14159265").unwrap()), "62831853/20000000");
+        assert_eq!(format!("{}", evaluate("1.5e-3 m").unwrap()), "3/2000 m");
+        // too many digits for a Rational, so inexact
+        assert!(evaluate("0.0000000000000000001").unwrap().inexact_reason().is_some());
+        assert!(evaluate("9223372036854775808").unwrap().inexact_reason().is_some());
+        // even if the nearest float could be made exact
+        assert_eq!(evaluate("1.00000000000000000001").unwrap().inexact_reason(), Some(value::InexactReason::FloatInput));
+        assert_eq!(evaluate("1e999"), Err(CalculatorError::OverflowError));
+        // but anything that fits in 64 bits is exact
+        assert_eq!(format!("{}", evaluate("3000000000").unwrap()), "3000000000");
+        assert_eq!(format!("{}", evaluate("0.0000000001").unwrap()), "1/10000000000");
+    }
+
+    #[test]
+    fn test_quantity() {
+        let show = |input| format!("{}", evaluate(input).expect(input));
+        assert_eq!(show("9.8 m/s^2"), "49/5 m/s^2");
+        assert_eq!(show("5 kg*m/s^2"), "5 N");
+        assert_eq!(show("5 kg m / s^2"), "5 N");
+        assert_eq!(show("9.8 m / s ^ 2"), "49/5 m/s^2");
+        assert_eq!(show("3 m s^-2"), "3 m/s^2");
+        assert_eq!(show("2m^2"), "2 m^2");
+        // the quantity is a single value, whatever the operators around it
+        assert_eq!(show("6 m / 2 s"), "3 m/s");
+        assert_eq!(show("1 / 4 m/s"), "1/4 s/m");
+        assert_eq!(show("7/2 m/s"), "7/2 m/s");
+        assert_eq!(show("3:4 km/hr"), "5/24 m/s");
+        assert_eq!(show("1/2m"), "1/2 m^-1");
+        match quantity(b"9.8 m/s^2") {
+            IResult::Done(rest, Expression::Mul(box Expression::Number(n), box Expression::Units(v, text))) => {
+                assert_eq!((rest, &n[..], &text[..]), (&b""[..], "9.8", "m/s^2"));
+                assert_eq!(v, units::get("m").unwrap() / units::get("s").unwrap() / units::get("s").unwrap());
+            },
+            res => panic!("{:?}", res),
+        }
+        // a keyword or a name that is not a unit ends the units
+        match quantity(b"10 km/hr in m/s") {
+            IResult::Done(rest, _) => assert_eq!(rest, &b" in m/s"[..]),
+            res => panic!("{:?}", res),
+        }
+        assert_eq!(calc::Calculator::new().run("2 m x"), Err(CalculatorError::SyntaxError));
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let two = || Expression::Number("2".to_string());
+        let three = || Expression::Number("3".to_string());
+        assert_eq!(parse_partial("2 3 +"), (Ok(Expression::Mul(Box::new(two()), Box::new(three()))), "+"));
+        assert_eq!(parse_partial(" 2 + 3"), (Ok(Expression::Add(Box::new(two()), Box::new(three()))), ""));
+        assert_eq!(parse_partial("2)"), (Ok(two()), ")"));
+        assert_eq!(parse_partial("2 * "), (Ok(two()), "* "));
+        assert_eq!(parse_partial("(2 + 3"), (Err(CalculatorError::SyntaxError), "(2 + 3"));
+        assert_eq!(parse_partial(""), (Err(CalculatorError::SyntaxError), ""));
+        assert_eq!(parse_partial("x := 2").1, ":= 2");
+    }
+
+    #[test]
+    fn test_error_position() {
+        assert_eq!(error_position("1 + * 2"), Some(4));
+        assert_eq!(error_position("1 + + 2"), None);
+        assert_eq!(error_position("2 +"), Some(3));
+        assert_eq!(error_position("2 +\n"), Some(3));
+        assert_eq!(error_position("2 3 )"), Some(4));
+        assert_eq!(error_position("(1 + 2"), Some(0));
+        assert_eq!(error_position("x = 1 * / 2"), Some(8));
+        assert_eq!(error_position("x := 2 ? 3"), Some(7));
+        assert_eq!(error_position("2 ± ± 3"), Some(5));
+        assert_eq!(error_position(""), Some(0));
+        assert_eq!(error_position("undefined + 1"), None);
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(format!("{}", CalculatorError::DivideByZeroError), "division by zero");
+        assert_eq!(format!("{}", CalculatorError::DomainError), "argument out of domain");
+        assert_eq!(format!("{}", CalculatorError::OverflowError), "numeric overflow");
+        assert_eq!(format!("{}", CalculatorError::UnitError), "incompatible units");
+        assert_eq!(format!("{}", CalculatorError::SyntaxError), "syntax error");
+        assert_eq!(format!("{:?}", CalculatorError::SyntaxError), "SyntaxError");
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(CalculatorError::DivideByZeroError.code(), "E_DIVZERO");
+        assert_eq!(CalculatorError::DomainError.code(), "E_DOMAIN");
+        assert_eq!(CalculatorError::OverflowError.code(), "E_OVERFLOW");
+        assert_eq!(CalculatorError::UnitError.code(), "E_UNIT");
+        assert_eq!(CalculatorError::SyntaxError.code(), "E_SYNTAX");
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        use value::ArithmeticError;
+        assert_eq!(CalculatorError::from(ArithmeticError::DivideByZeroError), CalculatorError::DivideByZeroError);
+        assert_eq!(CalculatorError::from(ArithmeticError::DomainError), CalculatorError::DomainError);
+        assert_eq!(CalculatorError::from(ArithmeticError::OverflowError), CalculatorError::OverflowError);
+        assert_eq!(CalculatorError::from(ArithmeticError::UnitError), CalculatorError::UnitError);
+    }
+}
+
@@ -0,0 +1,114 @@
+//! Values with an uncertainty (e.g. `10 ± 0.5`). Uncertainties are propagated linearly: the
+//! uncertainty of a result is the sum of how much each operand's uncertainty can change it.
+
+use uval::UnitValue;
+use value::{Value, ArithmeticError};
+
+use std::fmt;
+
+/// A value and its (non-negative) uncertainty, which has the same unit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Measurement {
+    /// The central value
+    pub value: UnitValue,
+    /// How far the actual value may be from the central value
+    pub uncertainty: UnitValue,
+}
+
+/// The absolute value of a value (keeping its unit)
+fn abs(a: &UnitValue) -> UnitValue {
+    if a.value.is_negative() { -*a } else { *a }
+}
+
+impl Measurement {
+    /// Create a measurement; the uncertainty must have the same unit as the value
+    /// (a zero uncertainty can be written without units)
+    pub fn new(value: UnitValue, uncertainty: UnitValue) -> Result<Measurement, ArithmeticError> {
+        if uncertainty.is_zero() {
+            return Ok(Measurement::exact(value))
+        }
+        if value.unit != uncertainty.unit {
+            return Err(ArithmeticError::UnitError)
+        }
+        Ok(Measurement { value: value, uncertainty: abs(&uncertainty) })
+    }
+    /// A value with no uncertainty
+    pub fn exact(value: UnitValue) -> Measurement {
+        Measurement { value: value, uncertainty: UnitValue::with_unit(Value::zero(), value.unit) }
+    }
+    /// (a ± da) + (b ± db) = (a + b) ± (da + db)
+    pub fn add(&self, other: &Measurement) -> Result<Measurement, ArithmeticError> {
+        Ok(Measurement {
+            value: try!(self.value.add(&other.value)),
+            uncertainty: try!(self.uncertainty.add(&other.uncertainty)),
+        })
+    }
+    /// (a ± da) - (b ± db) = (a - b) ± (da + db)
+    pub fn sub(&self, other: &Measurement) -> Result<Measurement, ArithmeticError> {
+        Ok(Measurement {
+            value: try!(self.value.sub(&other.value)),
+            uncertainty: try!(self.uncertainty.add(&other.uncertainty)),
+        })
+    }
+    /// (a ± da) * (b ± db) = ab ± (|b| da + |a| db)
+    pub fn mul(&self, other: &Measurement) -> Result<Measurement, ArithmeticError> {
+        let da = try!(abs(&other.value).mul(&self.uncertainty));
+        let db = try!(abs(&self.value).mul(&other.uncertainty));
+        Ok(Measurement {
+            value: try!(self.value.mul(&other.value)),
+            uncertainty: try!(da.add(&db)),
+        })
+    }
+    /// (a ± da) / (b ± db) = a/b ± (da / |b| + |a| db / b^2)
+    pub fn div(&self, other: &Measurement) -> Result<Measurement, ArithmeticError> {
+        let b = abs(&other.value);
+        let da = try!(self.uncertainty.div(&b));
+        let db = try!(try!(abs(&self.value).mul(&other.uncertainty)).div(&try!(b.mul(&b))));
+        Ok(Measurement {
+            value: try!(self.value.div(&other.value)),
+            uncertainty: try!(da.add(&db)),
+        })
+    }
+}
+
+impl fmt::Display for Measurement {
+    /// Display as `value ± uncertainty`
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} ± {}", self.value, self.uncertainty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uval::UnitValue;
+    use value::ArithmeticError;
+
+    /// A unitless measurement
+    fn m(value: f64, uncertainty: f64) -> Measurement {
+        Measurement::new(UnitValue::from_input(value).unwrap(), UnitValue::from_input(uncertainty).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_propagation() {
+        assert_eq!(m(10.0, 0.5).add(&m(2.0, 0.25)), Ok(m(12.0, 0.75)));
+        assert_eq!(m(10.0, 0.5).sub(&m(2.0, 0.25)), Ok(m(8.0, 0.75)));
+        assert_eq!(m(10.0, 0.5).mul(&m(2.0, 0.0)), Ok(m(20.0, 1.0)));
+        assert_eq!(m(10.0, 0.5).mul(&m(-2.0, 0.25)), Ok(m(-20.0, 3.5)));
+        assert_eq!(m(10.0, 0.5).div(&m(2.0, 0.0)), Ok(m(5.0, 0.25)));
+        assert_eq!(m(10.0, 0.5).div(&m(-2.0, 0.5)), Ok(m(-5.0, 1.5)));
+        assert_eq!(m(10.0, 0.5).div(&m(0.0, 0.5)), Err(ArithmeticError::DivideByZeroError));
+    }
+
+    #[test]
+    fn test_units() {
+        let meters = |a| UnitValue::meters(a).unwrap();
+        let a = Measurement::new(meters(10.0), meters(-0.5)).unwrap();
+        assert_eq!(a.uncertainty, meters(0.5));
+        assert_eq!(format!("{}", a), "10 m ± 1/2 m");
+        assert_eq!(format!("{}", a.mul(&a).unwrap()), "100 m^2 ± 10 m^2");
+        assert_eq!(Measurement::new(meters(10.0), UnitValue::seconds(1.0).unwrap()), Err(ArithmeticError::UnitError));
+        assert_eq!(Measurement::new(meters(10.0), UnitValue::zero()), Ok(Measurement::exact(meters(10.0))));
+        assert_eq!(a.add(&Measurement::exact(UnitValue::seconds(1.0).unwrap())), Err(ArithmeticError::UnitError));
+    }
+}
@@ -0,0 +1,200 @@
+//! Number theory functions. These operate on exact, unitless integers.
+
+use uval::UnitValue;
+use value::{Value, ArithmeticError, InexactReason};
+use unit::Unit;
+use rational::Rational;
+
+use std::cmp;
+use std::fmt::Write;
+
+/// Get a unitless, exact, positive integer from a value (otherwise DomainError)
+pub fn positive_integer(v: &UnitValue) -> Result<u32, ArithmeticError> {
+    match v.value.get_exact() {
+        Some(a) if v.unitless() && a.is_integer() && a.num > 0 && a.num <= u32::max_value() as i64 => Ok(a.num as u32),
+        _ => Err(ArithmeticError::DomainError),
+    }
+}
+
+/// Get a unitless, exact integer from a value (otherwise DomainError)
+pub fn integer(v: &UnitValue) -> Result<i64, ArithmeticError> {
+    match v.value.get_exact() {
+        Some(a) if v.unitless() && a.is_integer() => Ok(a.num),
+        _ => Err(ArithmeticError::DomainError),
+    }
+}
+
+/// Make an exact, unitless value from an integer
+pub fn integer_value(n: i64) -> Result<UnitValue, ArithmeticError> {
+    Rational::from_i64(n).map(|r| UnitValue::with_unit(Value::Exact(r), Unit::zero())).map_err(ArithmeticError::from)
+}
+
+/// Find the prime factorization of a positive integer, as (prime, exponent) pairs in
+/// increasing order (1 has no prime factors)
+// Uses trial division; this is fast enough since n < 2^32
+pub fn factorize(mut n: u32) -> Vec<(u32, u32)> {
+    let mut factors = Vec::new();
+    let mut p = 2;
+    // p <= n / p rather than p * p <= n, which could overflow
+    while p <= n / p {
+        let mut exp = 0;
+        while n % p == 0 {
+            n /= p;
+            exp += 1;
+        }
+        if exp > 0 {
+            factors.push((p, exp));
+        }
+        p += if p == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        factors.push((n, 1));
+    }
+    factors
+}
+
+/// Check whether a positive integer is prime (by trial division)
+pub fn is_prime(n: u32) -> bool {
+    n > 1 && (2..).take_while(|&p| p <= n / p).all(|p| n % p != 0)
+}
+
+/// The greatest common divisor of two integers (always non-negative; gcd(0, 0) = 0)
+pub fn gcd(a: i64, b: i64) -> i64 {
+    // neither can be i64::min_value(), so abs can't overflow
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// n! for a non-negative integer; exact if it fits in a Rational (n <= 20), otherwise inexact
+pub fn factorial(n: i64) -> Result<Value, ArithmeticError> {
+    if n < 0 {
+        return Err(ArithmeticError::DomainError)
+    }
+    // this overflows to infinity for n > 170; stop early rather than looping up to n
+    if n > 170 {
+        return Err(ArithmeticError::OverflowError)
+    }
+    match (1..n + 1).fold(Some(1i64), |acc, k| acc.and_then(|a| a.checked_mul(k))) {
+        Some(f) => Rational::from_i64(f).map(Value::Exact).map_err(ArithmeticError::from),
+        // too large for a Rational
+        None => Value::inexact((1..n + 1).fold(1.0, |acc, k| acc * k as f64), InexactReason::Overflow),
+    }
+}
+
+/// The number of ways to choose r of n items in order, n!/(n-r)! (0 if r > n)
+pub fn permutations(n: i64, r: i64) -> Result<i64, ArithmeticError> {
+    if n < 0 || r < 0 {
+        return Err(ArithmeticError::DomainError)
+    }
+    if r > n {
+        return Ok(0)
+    }
+    (n - r + 1..n + 1).fold(Ok(1), |acc, k| acc.and_then(|a: i64| a.checked_mul(k).ok_or(ArithmeticError::OverflowError)))
+}
+
+/// The number of ways to choose r of n items, n!/(r!(n-r)!) (0 if r > n)
+pub fn combinations(n: i64, r: i64) -> Result<i64, ArithmeticError> {
+    if n < 0 || r < 0 {
+        return Err(ArithmeticError::DomainError)
+    }
+    if r > n {
+        return Ok(0)
+    }
+    // C(n, k + 1) = C(n, k) (n - k) / (k + 1), dividing out the common factor first so the only
+    // way to overflow is for C(n, k + 1) itself to be too large
+    let mut c = 1;
+    for k in 0..cmp::min(r, n - r) {
+        let g = gcd(c, k + 1);
+        c = try!((c / g).checked_mul((n - k) / ((k + 1) / g)).ok_or(ArithmeticError::OverflowError));
+    }
+    Ok(c)
+}
+
+/// Format a prime factorization, e.g. `2^3 * 3^2 * 5` (the empty product is `1`)
+pub fn format_factors(factors: &[(u32, u32)]) -> String {
+    let mut s = String::new();
+    for &(p, exp) in factors {
+        if !s.is_empty() {
+            s.push_str(" * ");
+        }
+        if exp == 1 {
+            write!(s, "{}", p).unwrap();
+        } else {
+            write!(s, "{}^{}", p, exp).unwrap();
+        }
+    }
+    if s.is_empty() {
+        s.push('1');
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factorize() {
+        assert_eq!(factorize(360), vec![(2, 3), (3, 2), (5, 1)]);
+        assert_eq!(factorize(97), vec![(97, 1)]);
+        assert_eq!(factorize(1), vec![]);
+        assert_eq!(factorize(2), vec![(2, 1)]);
+        assert_eq!(factorize(2147483647), vec![(2147483647, 1)]);
+        assert_eq!(factorize(2147395600), vec![(2, 4), (5, 2), (7, 2), (331, 2)]);
+        // near u32::MAX, squaring the trial divisor would overflow
+        assert_eq!(factorize(4294967291), vec![(4294967291, 1)]);
+        assert_eq!(factorize(4294967295), vec![(3, 1), (5, 1), (17, 1), (257, 1), (65537, 1)]);
+        assert_eq!(format_factors(&factorize(360)), "2^3 * 3^2 * 5");
+        assert_eq!(format_factors(&factorize(1)), "1");
+    }
+
+    #[test]
+    fn test_gcd_factorial() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(-12, 18), 6);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(gcd(0, 0), 0);
+        assert_eq!(factorial(0), Ok(Value::Exact(Rational::from_integer(1).unwrap())));
+        assert_eq!(factorial(12), Ok(Value::Exact(Rational::from_integer(479001600).unwrap())));
+        assert_eq!(factorial(20), Ok(Value::Exact(Rational::from_i64(2432902008176640000).unwrap())));
+        assert_eq!(factorial(21).map(|a| a.get_exact().is_none()), Ok(true));
+        assert_eq!(factorial(171), Err(ArithmeticError::OverflowError));
+        assert_eq!(factorial(-1), Err(ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_combinatorics() {
+        assert_eq!(combinations(5, 2), Ok(10));
+        assert_eq!(combinations(5, 0), Ok(1));
+        assert_eq!(combinations(5, 5), Ok(1));
+        assert_eq!(combinations(2, 5), Ok(0));
+        assert_eq!(combinations(33, 16), Ok(1166803110));
+        assert_eq!(combinations(40, 20), Ok(137846528820));
+        assert_eq!(combinations(66, 33), Ok(7219428434016265740));
+        assert_eq!(combinations(68, 34), Err(ArithmeticError::OverflowError));
+        assert_eq!(combinations(-1, 0), Err(ArithmeticError::DomainError));
+        assert_eq!(permutations(5, 2), Ok(20));
+        assert_eq!(permutations(5, 0), Ok(1));
+        assert_eq!(permutations(2, 5), Ok(0));
+        assert_eq!(permutations(20, 20), Ok(2432902008176640000));
+        assert_eq!(permutations(21, 21), Err(ArithmeticError::OverflowError));
+        assert_eq!(permutations(5, -1), Err(ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_is_prime() {
+        let primes = [2, 3, 5, 7, 97, 7919, 65521, 2147483647, 4294967291];
+        let composites = [1, 4, 9, 91, 7917, 65536, 2147395600, 2147483645, 4294967295];
+        for &p in primes.iter() {
+            assert!(is_prime(p), "{} is prime", p);
+        }
+        for &c in composites.iter() {
+            assert!(!is_prime(c), "{} is not prime", c);
+        }
+    }
+}
@@ -7,29 +7,66 @@ use std::fmt;
 
 /// Rational numbers. The following are invariants:
 ///
-/// * Both numerator and denominator are between `i32::min_value() + 1`
-///   and `i32::max_value()`, inclusive. (This is so that negation and
-///   casting between `i32` and `u32` are always valid.) Any operation
+/// * Both numerator and denominator are between `i64::min_value() + 1`
+///   and `i64::max_value()`, inclusive. (This is so that negation and
+///   casting between `i64` and `u64` are always valid.) Any operation
 ///   that would cause this to be false would return `Err(OverflowError)`.
 /// * The denominator is always positive. An operation that would
 ///   cause the denominator to be zero would return `Err(OverflowError)`.
 #[derive(Copy, Clone, Hash, Debug, PartialEq, Eq)]
 pub struct Rational {
     /// Simplified numerator
-    pub num: i32,
+    pub num: i64,
     /// Simplified denominator
-    pub den: u32,
+    pub den: u64,
 }
 
 /// An operation caused a value to overflow
 #[derive(Debug, PartialEq, Eq)]
 pub struct OverflowError;
 
+/// How a value halfway between two integers is rounded
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RoundingMode {
+    /// To the even integer (banker's rounding): 2.5 is 2 and 3.5 is 4
+    HalfEven,
+    /// Up: 2.5 is 3 and -2.5 is -2
+    HalfUp,
+    /// Away from zero: 2.5 is 3 and -2.5 is -3
+    HalfAway,
+}
+
+impl Default for RoundingMode {
+    fn default() -> RoundingMode {
+        RoundingMode::HalfEven
+    }
+}
+
+impl RoundingMode {
+    /// Round a float to the nearest integer
+    pub fn round_float(self, a: f64) -> f64 {
+        let floor = a.floor();
+        if a - floor != 0.5 {
+            return a.round()
+        }
+        match self {
+            RoundingMode::HalfEven => if floor % 2.0 == 0.0 { floor } else { floor + 1.0 },
+            RoundingMode::HalfUp => floor + 1.0,
+            RoundingMode::HalfAway => a.round(),
+        }
+    }
+}
+
+/// Like try!, but for Options
+macro_rules! try_opt {
+    ($e:expr) => (match $e { Some(a) => a, None => return None })
+}
+
 /// Exponentiation, but also check for integer overflow.
 // Uses exponentiation by squaring
 #[inline]
-fn checked_pow(mut base: i32, mut exp: u32) -> Result<i32, OverflowError> {
-    let mut acc: i32 = 1;
+fn checked_pow(mut base: i64, mut exp: u32) -> Result<i64, OverflowError> {
+    let mut acc: i64 = 1;
     while exp > 1 {
         if (exp & 1) == 1 {
             acc = try!(acc.checked_mul(base).ok_or(OverflowError));
@@ -43,6 +80,28 @@ fn checked_pow(mut base: i32, mut exp: u32) -> Result<i32, OverflowError> {
     Ok(acc)
 }
 
+/// Checked power of a denominator. Intermediate products are computed in u128 and must stay
+/// within the range of a denominator (at most i64::MAX).
+fn checked_pow_den(base: u64, mut exp: u32) -> Result<u64, OverflowError> {
+    /// Multiply, failing if the result is not a valid denominator
+    fn mul(a: u128, b: u128) -> Result<u128, OverflowError> {
+        // both are at most i64::MAX, so this can't overflow a u128
+        if a * b <= i64::max_value() as u128 { Ok(a * b) } else { Err(OverflowError) }
+    }
+    let (mut base, mut acc) = (base as u128, 1u128);
+    while exp > 1 {
+        if (exp & 1) == 1 {
+            acc = try!(mul(acc, base));
+        }
+        exp /= 2;
+        base = try!(mul(base, base));
+    }
+    if exp == 1 {
+        acc = try!(mul(acc, base));
+    }
+    Ok(acc as u64)
+}
+
 /// Find the greatest common divisor of two integers.
 /// The result has the same sign as the denominator `n`, or the sign
 /// of the numerator `m` if it is zero.
@@ -61,7 +120,7 @@ fn checked_pow(mut base: i32, mut exp: u32) -> Result<i32, OverflowError> {
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.:
 #[inline]
-fn gcd(mut m: i32, mut n: i32) -> i32 {
+fn gcd(mut m: i64, mut n: i64) -> i64 {
     // Use Stein's algorithm
     if m == 0 || n == 0 { return m | n }
 
@@ -76,8 +135,8 @@ fn gcd(mut m: i32, mut n: i32) -> i32 {
     // Assuming two's complement, the number created by the shift
     // is positive for all numbers except gcd = abs(min value)
     // The call to .abs() causes a panic in debug mode
-    if m == i32::min_value() || n == i32::min_value() {
-        return (1 << shift) as i32
+    if m == i64::min_value() || n == i64::min_value() {
+        return (1 << shift) as i64
     }
 
     // guaranteed to be positive now, rest like unsigned algorithm
@@ -108,23 +167,23 @@ trait CheckableOverflow<T> {
 impl CheckableOverflow<Rational> for Rational {
     #[inline]
     fn check_overflow(self) -> Result<Rational, OverflowError> {
-        if self.num > i32::min_value() && self.den > 0 && self.den <= (i32::max_value() as u32) { Ok(self) } else { Err(OverflowError) }
+        if self.num > i64::min_value() && self.den > 0 && self.den <= (i64::max_value() as u64) { Ok(self) } else { Err(OverflowError) }
     }
 }
 
 /// Check an unsigned int for overflow
-impl CheckableOverflow<u32> for u32 {
+impl CheckableOverflow<u64> for u64 {
     #[inline]
-    fn check_overflow(self) -> Result<u32, OverflowError> {
-        if self > 0 && self <= (i32::max_value() as u32) { Ok(self) } else { Err(OverflowError) }
+    fn check_overflow(self) -> Result<u64, OverflowError> {
+        if self > 0 && self <= (i64::max_value() as u64) { Ok(self) } else { Err(OverflowError) }
     }
 }
 
 /// Check a signed int for overflow
-impl CheckableOverflow<i32> for i32 {
+impl CheckableOverflow<i64> for i64 {
     #[inline]
-    fn check_overflow(self) -> Result<i32, OverflowError> {
-        if self > i32::min_value() { Ok(self) } else { Err(OverflowError) }
+    fn check_overflow(self) -> Result<i64, OverflowError> {
+        if self > i64::min_value() { Ok(self) } else { Err(OverflowError) }
     }
 }
 
@@ -168,9 +227,17 @@ impl Rational {
             den: 1,
         }
     }
-    /// Rational from an integer (need to check if it's outside Rational range)
+    /// Rational from an integer (every `i32` fits, since the numerator is stored as an `i64`)
     #[inline]
     pub fn from_integer(i: i32) -> Result<Rational, OverflowError> {
+        Ok(Rational {
+            num: try!((i as i64).check_overflow()),
+            den: 1,
+        })
+    }
+    /// Rational from a 64-bit integer (only `i64::min_value()` is out of range)
+    #[inline]
+    pub fn from_i64(i: i64) -> Result<Rational, OverflowError> {
         Ok(Rational {
             num: try!(i.check_overflow()),
             den: 1,
@@ -178,13 +245,17 @@ impl Rational {
     }
     /// Create a Rational from numerator and denominator, and simplify
     pub fn new(num: i32, den: i32) -> Result<Rational, OverflowError> {
+        Rational::new_i64(num as i64, den as i64)
+    }
+    /// Create a Rational from a 64-bit numerator and denominator, and simplify
+    pub fn new_i64(num: i64, den: i64) -> Result<Rational, OverflowError> {
         if den == 0 {
             panic!("denominator = 0");
         }
         let gcd = gcd(num, den);
         Rational {
             num: num / gcd,
-            den: (den / gcd) as u32, // guaranteed to be positive
+            den: (den / gcd) as u64, // guaranteed to be positive
         }.check_overflow()
     }
     /// Negate and return the result.
@@ -200,14 +271,14 @@ impl Rational {
     pub fn recip(&self) -> Result<Rational, OverflowError> {
         if self.num > 0 {
             Ok(Rational {
-                num: self.den as i32,
-                den: self.num as u32,
+                num: self.den as i64,
+                den: self.num as u64,
             })
         } else {
             if self.num != 0 {
                 Ok(Rational {
-                    num: -(self.den as i32),
-                    den: (-self.num) as u32,
+                    num: -(self.den as i64),
+                    den: (-self.num) as u64,
                 })
             } else {
                 Err(OverflowError)
@@ -234,6 +305,117 @@ impl Rational {
     pub fn is_negative(&self) -> bool {
         self.num < 0
     }
+    /// Round towards zero
+    #[inline]
+    pub fn trunc(&self) -> Rational {
+        Rational { num: self.num / self.den as i64, den: 1 }
+    }
+    /// Split into a whole number and a proper fraction with the same sign, which add up to
+    /// this (e.g. 7/2 is 3 and 1/2, and -7/2 is -3 and -1/2)
+    #[inline]
+    pub fn to_mixed_number(&self) -> (i64, Rational) {
+        (self.num / self.den as i64, Rational { num: self.num % self.den as i64, den: self.den })
+    }
+    /// Write as a mixed number, e.g. 3 1/2 or -3 1/2. A proper fraction (e.g. 1/2) or an
+    /// integer is written as usual.
+    pub fn to_mixed_string(&self) -> String {
+        match self.to_mixed_number() {
+            (whole, frac) if whole != 0 && !frac.is_zero() => format!("{} {}", whole, if frac.is_negative() { frac.negate() } else { frac }),
+            _ => format!("{}", self),
+        }
+    }
+    /// Round down
+    #[inline]
+    pub fn floor(&self) -> Rational {
+        let t = self.trunc();
+        if self.is_negative() && !self.is_integer() { Rational { num: t.num - 1, den: 1 } } else { t }
+    }
+    /// Round up
+    #[inline]
+    pub fn ceil(&self) -> Rational {
+        let t = self.trunc();
+        if !self.is_negative() && !self.is_integer() { Rational { num: t.num + 1, den: 1 } } else { t }
+    }
+    /// Round to the nearest integer (halfway cases away from zero, like f64::round)
+    pub fn round(&self) -> Rational {
+        let t = self.trunc();
+        // |remainder| >= den / 2 (the remainder is less than den, so doubling it can't overflow)
+        if (self.num % self.den as i64).abs() as u64 * 2 >= self.den {
+            Rational { num: t.num + self.num.signum(), den: 1 }
+        } else {
+            t
+        }
+    }
+    /// Round to the nearest integer, with halfway cases rounded according to `mode`
+    pub fn round_with_mode(&self, mode: RoundingMode) -> Rational {
+        let t = self.trunc();
+        // the remainder is less than den, so doubling it can't overflow
+        let twice = (self.num % self.den as i64).abs() as u64 * 2;
+        let away = Rational { num: t.num + self.num.signum(), den: 1 };
+        if twice != self.den {
+            return if twice > self.den { away } else { t }
+        }
+        match mode {
+            RoundingMode::HalfEven => if t.num % 2 == 0 { t } else { away },
+            RoundingMode::HalfUp => if self.is_negative() { t } else { away },
+            RoundingMode::HalfAway => away,
+        }
+    }
+    /// Write as a decimal, using at most `max_digits` digits after the decimal point. A
+    /// repeating decimal has its repeating digits in parentheses (e.g. 1/6 is `0.1(6)`); if
+    /// they don't fit, the digits are cut off and followed by `...`.
+    pub fn to_decimal(&self, max_digits: usize) -> String {
+        let sign = if self.num < 0 { "-" } else { "" };
+        // the remainder is multiplied by 10, which could overflow a u64
+        let (num, den) = (self.num.abs() as u128, self.den as u128);
+        let int = format!("{}{}", sign, num / den);
+        let mut rem = num % den;
+        if rem == 0 {
+            return int
+        }
+        // long division, remembering the remainder before each digit to find where it repeats
+        let mut digits = String::new();
+        let mut rems = Vec::new();
+        while rem != 0 {
+            if let Some(i) = rems.iter().position(|&r| r == rem) {
+                return format!("{}.{}({})", int, &digits[..i], &digits[i..])
+            }
+            if rems.len() == max_digits {
+                return format!("{}.{}...", int, digits)
+            }
+            rems.push(rem);
+            rem *= 10;
+            digits.push((b'0' + (rem / den) as u8) as char);
+            rem %= den;
+        }
+        format!("{}.{}", int, digits)
+    }
+    /// Write the value exactly in scientific notation (e.g. 1.5e3 for 1500), if its decimal
+    /// expansion terminates (None for e.g. 1/3)
+    pub fn to_scientific(&self) -> Option<String> {
+        let mut den = self.den;
+        while den % 2 == 0 { den /= 2 }
+        while den % 5 == 0 { den /= 5 }
+        if den != 1 {
+            return None
+        }
+        // the denominator is at most 2^63, so there are at most 63 digits after the point
+        let decimal = self.to_decimal(64);
+        let (sign, decimal) = if decimal.starts_with('-') { ("-", &decimal[1..]) } else { ("", &decimal[..]) };
+        let point = decimal.find('.').unwrap_or(decimal.len()) as i32;
+        let digits: String = decimal.chars().filter(|&c| c != '.').collect();
+        let first = match digits.find(|c| c != '0') {
+            Some(i) => i,
+            None => return Some("0e0".to_owned()),
+        };
+        let digits = digits[first..].trim_right_matches('0');
+        let exp = point - first as i32 - 1;
+        Some(if digits.len() == 1 {
+            format!("{}{}e{}", sign, digits, exp)
+        } else {
+            format!("{}{}.{}e{}", sign, &digits[..1], &digits[1..], exp)
+        })
+    }
     /// Take the 'exp'th power
     #[inline]
     pub fn pow(&self, exp: i32) -> Result<Rational, OverflowError> {
@@ -242,7 +424,7 @@ impl Rational {
                 // power of both
                 Rational {
                     num: try!(checked_pow(self.num, exp as u32)),
-                    den: try!(checked_pow(self.den as i32, exp as u32)) as u32,
+                    den: try!(checked_pow_den(self.den, exp as u32)),
                 }.check_overflow()
             } else {
                 // must check for this (-2^31 is valid, but not +2^31, so doing this would cause a panic.)
@@ -261,15 +443,87 @@ impl Rational {
             Ok(Rational { num: 1, den: 1 })
         }
     }
+    /// Take the exact 'n'th root, or None if it is not rational.
+    /// Negative numbers only have odd roots.
+    pub fn root(&self, n: u32) -> Option<Rational> {
+        /// Find the integer root of a positive integer (if it exists)
+        fn int_root(a: i64, n: u32) -> Option<i64> {
+            // the float root is close to the integer root, so check its neighbors too
+            let r = (a as f64).powf(1.0 / n as f64).round() as i64;
+            (cmp::max(r, 1) - 1..r.saturating_add(2)).find(|&c| checked_pow(c, n) == Ok(a))
+        }
+        match n {
+            0 => return None,
+            // the float root of a number near i64::MAX may round past it
+            1 => return Some(*self),
+            _ => (),
+        }
+        if self.is_negative() {
+            return if n % 2 == 1 { self.negate().root(n).map(|r| r.negate()) } else { None }
+        }
+        // the root of a simplified fraction is also simplified
+        Some(Rational {
+            num: try_opt!(int_root(self.num, n)),
+            den: try_opt!(int_root(self.den as i64, n)) as u64,
+        })
+    }
+    /// The mediant (a+c)/(b+d) of two fractions a/b and c/d, which lies between them.
+    pub fn mediant(&self, other: &Rational) -> Result<Rational, OverflowError> {
+        Rational::new_i64(try!(self.num.checked_add(other.num).ok_or(OverflowError)),
+                          try!((self.den as i64).checked_add(other.den as i64).ok_or(OverflowError)))
+    }
+    /// Find the fraction closest to `target` with a denominator of at most `max_den`.
+    /// Returns `Err(OverflowError)` if there is none (`max_den` is 0 or the target is out of range).
+    // Searches the Stern-Brocot tree: the bounds lower = a/b and upper = c/d start at 0/1 and
+    // 1/0, and the bound on the same side of the target as their mediant is replaced by it.
+    // Consecutive steps in the same direction are taken all at once, so this is fast even for
+    // large denominators.
+    pub fn best_approximation(target: f64, max_den: u32) -> Result<Rational, OverflowError> {
+        if max_den == 0 || !(target.abs() <= i32::max_value() as f64) {
+            return Err(OverflowError)
+        }
+        let max_den = cmp::min(max_den, i32::max_value() as u32) as i64;
+        let x = target.abs();
+        let (mut a, mut b, mut c, mut d) = (0i64, 1i64, 1i64, 0i64);
+        while b + d <= max_den {
+            let m = (a + c) as f64 / (b + d) as f64;
+            if m == x {
+                a += c;
+                b += d;
+                c = a;
+                d = b;
+                break
+            } else if m < x {
+                // replace the lower bound with (a + kc)/(b + kd) for the largest k that stays below x
+                let k = ((x * b as f64 - a as f64) / (c as f64 - x * d as f64)).floor() as i64;
+                let k = if d == 0 { k } else { cmp::min(k, (max_den - b) / d) };
+                let k = cmp::max(k, 1);
+                a += k * c;
+                b += k * d;
+            } else {
+                // replace the upper bound with (c + ka)/(d + kb) for the largest k that stays above x
+                let k = ((c as f64 - x * d as f64) / (x * b as f64 - a as f64)).floor() as i64;
+                let k = cmp::max(cmp::min(k, (max_den - d) / b), 1);
+                c += k * a;
+                d += k * b;
+            }
+        }
+        // pick the closer bound (the upper bound may still be 1/0)
+        let (num, den) = if d == 0 || x - (a as f64 / b as f64) <= (c as f64 / d as f64) - x { (a, b) } else { (c, d) };
+        if num > i32::max_value() as i64 {
+            return Err(OverflowError)
+        }
+        Rational::new(if target < 0.0 { -num as i32 } else { num as i32 }, den as i32)
+    }
     /// Multiply two rational numbers.
     pub fn mul(&self, other: &Rational) -> Result<Rational, OverflowError> {
         // if possible, straight multiply then simplify
-        match (self.num.checked_mul(other.num), (self.den as i32).checked_mul(other.den as i32)) {
+        match (self.num.checked_mul(other.num), (self.den as i64).checked_mul(other.den as i64)) {
             (Some(np), Some(dp)) => {
                 let gcd = gcd(np, dp); // guaranteed positive
                 Rational {
                     num: np / gcd,
-                    den: (dp / gcd) as u32,
+                    den: (dp / gcd) as u64,
                 }.check_overflow()
             },
             // we overflowed; try to simplify first
@@ -279,12 +533,13 @@ impl Rational {
                 // (a / @1 * b / @2) / (c / @2 * d / @1)
                 // We find n1d2 and n2d1 which are the largest
                 // factors of a, d and b, c to avoid overflow as much
-                // as possible.
-                let n1d2 = gcd(self.num, other.den as i32);
-                let n2d1 = gcd(self.den as i32, other.num);
+                // as possible. gcd takes the sign of its second argument, so the sign is kept
+                // in the numerators.
+                let n1d2 = gcd(self.num, other.den as i64).abs();
+                let n2d1 = gcd(self.den as i64, other.num).abs();
                 Rational {
                     num: try!((self.num / n1d2).checked_mul(other.num / n2d1).ok_or(OverflowError)),
-                    den: try!((self.den as i32 / n2d1).checked_mul(other.den as i32 / n1d2).ok_or(OverflowError)) as u32,
+                    den: try!((self.den as i64 / n2d1).checked_mul(other.den as i64 / n1d2).ok_or(OverflowError)) as u64,
                 }.check_overflow()
             },
         }
@@ -297,16 +552,18 @@ impl Rational {
     /// Add two rationals
     pub fn add(&self, other: &Rational) -> Result<Rational, OverflowError> {
         // Find the gcd of denominators
-        let dgcd = gcd(self.den as i32, other.den as i32) as u32;
+        let dgcd = gcd(self.den as i64, other.den as i64) as u64;
         let a = self.den / dgcd;
         let b = other.den / dgcd;
-        // LCM = a * b / gcd(a, b); small shortcut
-        let denom = try!(self.den.checked_mul(b).ok_or(OverflowError));
-        // denom / self.den = b
-        // denom / other.den = a
+        // over the common denominator self.den * b = other.den * a
+        let num = try!(self.num.checked_mul(b as i64)
+                       .and_then(|x| other.num.checked_mul(a as i64).and_then(|y| x.checked_add(y)))
+                       .ok_or(OverflowError));
+        // any common factor of the sum and the denominator also divides dgcd
+        let g = gcd(num, dgcd as i64) as u64;
         Rational {
-            num: self.num * b as i32 + other.num * a as i32,
-            den: denom,
+            num: num / g as i64,
+            den: try!(a.checked_mul(other.den / g).ok_or(OverflowError)),
         }.check_overflow()
     }
     /// Subtract two rationals; a - b = a + -b.
@@ -319,37 +576,8 @@ impl Rational {
 impl Ord for Rational {
     /// Compare two rational numbers.
     fn cmp(&self, other: &Rational) -> cmp::Ordering {
-        // if signs vary, just compare numerators (denominators are always positive)
-        if self.is_negative() != other.is_negative() {
-            return self.num.cmp(&other.num)
-        }
-        // if both negative, reverse the comparison of the opposites.
-        if self.is_negative() {
-            return self.negate().cmp(&other.negate()).reverse()
-        }
-        // try to cross-multiply
-        match (self.num.checked_mul(other.den as i32), (self.den as i32).checked_mul(other.num)) {
-            (Some(a), Some(b)) => a.cmp(&b),
-            _ => {
-                // integer overflow with direct comparison
-                if self.den == other.den {
-                    return self.num.cmp(&other.num)
-                }
-                if self.num == other.num {
-                    return other.den.cmp(&self.den)
-                }
-                // do partial fraction comparison (mixed number)
-                let (ai, af) = (self.num / self.den as i32, self.num % self.den as i32);
-                let (bi, bf) = (other.num / other.den as i32, other.num % other.den as i32);
-                if ai > bi {
-                    cmp::Ordering::Greater
-                } else if ai < bi {
-                    cmp::Ordering::Less
-                } else { // partial fraction comparison
-                    (Rational { num: other.den as i32, den: bf as u32 }).cmp(&Rational { num: self.den as i32, den: af as u32 })
-                }
-            }
-        }
+        // cross-multiply; an i64 times a u64 always fits in an i128
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
     }
 }
 
@@ -405,12 +633,13 @@ mod tests {
         for m in nums.into_iter() {
             let n = *m;
             assert_eq!(Rational::new(n, 1), Rational::from_integer(n));
-            if n != i32::min_value() {
-                assert!(Rational::from_integer(n).unwrap().is_integer());
-            } else {
-                assert_eq!(Rational::from_integer(n), Err(OverflowError));
-            }
+            // every i32 fits, even the minimum
+            assert!(Rational::from_integer(n).unwrap().is_integer());
         }
+        assert_eq!(Rational::from_i64(i64::max_value()), Ok(Rational { num: i64::max_value(), den: 1 }));
+        assert_eq!(Rational::from_i64(i64::min_value()), Err(OverflowError));
+        assert_eq!(Rational::new_i64(10000000000, -4), Ok(Rational { num: -2500000000, den: 1 }));
+        assert_eq!(Rational::new_i64(i64::min_value(), 2), Ok(Rational { num: -(1 << 62), den: 1 }));
     }
 
     #[test]
@@ -419,6 +648,145 @@ mod tests {
         assert_eq!(rat!(3, 2).pow(-16), Rational::new(65536, 43046721));
         assert_eq!(rat!(26, 72).pow(200), Err(OverflowError));
         assert_eq!(rat!(26, 72).pow(-200), Err(OverflowError));
+        assert_eq!(rat!(2, 3).pow(-2), Ok(rat!(9, 4)));
+        assert_eq!(rat!(-2, 1).pow(-3), Ok(rat!(-1, 8)));
+        assert_eq!(rat!(0, 1).pow(-1), Err(OverflowError));
+        // the exponent can't be negated
+        assert_eq!(rat!(1, 1).pow(i32::min_value()), Ok(rat!(1, 1)));
+        assert_eq!(rat!(-1, 1).pow(i32::min_value()), Ok(rat!(1, 1)));
+        assert_eq!(rat!(2, 1).pow(i32::min_value()), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_pow_large_denominator() {
+        assert_eq!(rat!(1, 46341).pow(2), Ok(Rational { num: 1, den: 2147488281 }));
+        // 3037000499^2 < 2^63 - 1 < 3037000500^2
+        let big = Rational { num: 1, den: 3037000499 };
+        assert_eq!(big.pow(2), Ok(Rational { num: 1, den: 9223372030926249001 }));
+        assert_eq!(Rational { num: 1, den: 3037000500 }.pow(2), Err(OverflowError));
+        assert_eq!(rat!(1, 2097151).pow(3), Ok(Rational { num: 1, den: 9223358842721533951 }));
+        assert_eq!(rat!(1, 2097152).pow(3), Err(OverflowError));
+        assert_eq!(rat!(1, 2147483647).pow(2), Ok(Rational { num: 1, den: 4611686014132420609 }));
+        assert_eq!(rat!(1, 2147483647).pow(3), Err(OverflowError));
+        assert_eq!(rat!(1, 2).pow(62), Ok(Rational { num: 1, den: 1 << 62 }));
+        assert_eq!(rat!(1, 2).pow(63), Err(OverflowError));
+        assert_eq!(rat!(3, 1024).pow(-3), Ok(rat!(1073741824, 27)));
+        assert_eq!(rat!(1, 1).pow(i32::max_value()), Ok(rat!(1, 1)));
+    }
+
+    #[test]
+    fn test_neg() {
+        assert_eq!(-rat!(2, 3), rat!(-2, 3));
+        assert_eq!(-rat!(-2, 3), rat!(2, 3));
+        assert_eq!(-rat!(i32::max_value(), 1), rat!(-i32::max_value(), 1));
+        assert_eq!(-rat!(2, 3), rat!(2, 3).negate());
+    }
+
+    #[test]
+    fn test_mul_cross_cancel() {
+        let max = i64::max_value();
+        // the direct products overflow, so these go through cross-cancellation
+        assert_eq!(Rational { num: max, den: 2 }.mul(&Rational { num: 4, den: max as u64 }), Ok(rat!(2, 1)));
+        assert_eq!(Rational { num: -max, den: 1000000007 }.mul(&Rational { num: 1000000007, den: max as u64 }), Ok(rat!(-1, 1)));
+        assert_eq!(Rational { num: max - 1, den: max as u64 }.mul(&Rational { num: max, den: (max / 2) as u64 }), Ok(rat!(2, 1)));
+        // negative operands on either side
+        assert_eq!(Rational { num: max, den: 1000000007 }.mul(&Rational { num: -1000000007, den: max as u64 }), Ok(rat!(-1, 1)));
+        assert_eq!(Rational { num: -max, den: 1000000007 }.mul(&Rational { num: -1000000007, den: max as u64 }), Ok(rat!(1, 1)));
+        assert_eq!(Rational { num: -4, den: max as u64 }.mul(&Rational { num: max, den: 2 }), Ok(rat!(-2, 1)));
+        // no common factors, so it still overflows
+        assert_eq!(Rational { num: max, den: 1 }.mul(&rat!(2, 1)), Err(OverflowError));
+        assert_eq!(Rational { num: 1, den: max as u64 }.mul(&rat!(1, 3)), Err(OverflowError));
+        // products of i32 values always fit
+        assert_eq!(rat!(i32::max_value(), 1).mul(&rat!(2, 1)), Ok(Rational { num: 4294967294, den: 1 }));
+        assert_eq!(rat!(1, i32::max_value()).mul(&rat!(1, i32::max_value())), Ok(Rational { num: 1, den: 4611686014132420609 }));
+    }
+
+    #[test]
+    fn test_root() {
+        assert_eq!(rat!(27, 8).root(3), Some(rat!(3, 2)));
+        assert_eq!(rat!(-27, 8).root(3), Some(rat!(-3, 2)));
+        assert_eq!(rat!(-4, 1).root(2), None);
+        assert_eq!(rat!(2, 1).root(2), None);
+        assert_eq!(rat!(1, 4).root(2), Some(rat!(1, 2)));
+        assert_eq!(rat!(0, 1).root(5), Some(rat!(0, 1)));
+        assert_eq!(rat!(1, 1).root(1000000), Some(rat!(1, 1)));
+        assert_eq!(rat!(5, 7).root(1), Some(rat!(5, 7)));
+        assert_eq!(rat!(2147395600, 1).root(2), Some(rat!(46340, 1)));
+        assert_eq!(rat!(2147483647, 1).root(2), None);
+        assert_eq!(Rational { num: 9223372030926249001, den: 1 }.root(2), Some(Rational { num: 3037000499, den: 1 }));
+        assert_eq!(Rational { num: i64::max_value(), den: 1 }.root(1), Some(Rational { num: i64::max_value(), den: 1 }));
+        assert_eq!(rat!(1, 1).root(0), None);
+    }
+
+    #[test]
+    fn test_mediant() {
+        assert_eq!(rat!(1, 2).mediant(&rat!(2, 3)), Ok(rat!(3, 5)));
+        assert_eq!(rat!(0, 1).mediant(&rat!(1, 1)), Ok(rat!(1, 2)));
+        assert_eq!(rat!(i32::max_value(), 1).mediant(&rat!(1, 1)), Ok(rat!(1073741824, 1)));
+        assert_eq!(Rational { num: i64::max_value(), den: 1 }.mediant(&rat!(1, 1)), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_best_approximation() {
+        use std::f64::consts::PI;
+        assert_eq!(Rational::best_approximation(PI, 1), Ok(rat!(3, 1)));
+        assert_eq!(Rational::best_approximation(PI, 7), Ok(rat!(22, 7)));
+        assert_eq!(Rational::best_approximation(PI, 56), Ok(rat!(22, 7)));
+        // not a continued fraction convergent
+        assert_eq!(Rational::best_approximation(PI, 57), Ok(rat!(179, 57)));
+        assert_eq!(Rational::best_approximation(PI, 113), Ok(rat!(355, 113)));
+        assert_eq!(Rational::best_approximation(PI, 16603), Ok(rat!(355, 113)));
+        assert_eq!(Rational::best_approximation(-PI, 7), Ok(rat!(-22, 7)));
+        assert_eq!(Rational::best_approximation(1.0 / 3.0, 100), Ok(rat!(1, 3)));
+        assert_eq!(Rational::best_approximation(0.0, 100), Ok(rat!(0, 1)));
+        assert_eq!(Rational::best_approximation(0.75, 4), Ok(rat!(3, 4)));
+        assert_eq!(Rational::best_approximation(1e9 + 0.5, 2), Ok(rat!(2000000001, 2)));
+        assert_eq!(Rational::best_approximation(0.001, 10), Ok(rat!(0, 1)));
+        assert_eq!(Rational::best_approximation(PI, 0), Err(OverflowError));
+        assert_eq!(Rational::best_approximation(1e10, 1), Err(OverflowError));
+        assert_eq!(Rational::best_approximation(::std::f64::NAN, 1), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_add() {
+        assert_eq!(rat!(1, 6).add(&rat!(1, 3)), Ok(rat!(1, 2)));
+        assert_eq!(rat!(1, 6).sub(&rat!(1, 6)), Ok(rat!(0, 1)));
+        assert_eq!(rat!(i32::max_value(), 1).add(&rat!(i32::max_value(), 1)), Ok(Rational { num: 4294967294, den: 1 }));
+        assert_eq!(rat!(1, 2147483647).add(&rat!(1, 2147483646)), Ok(Rational { num: 4294967293, den: 4611686011984936962 }));
+        assert_eq!(Rational { num: i64::max_value(), den: 1 }.add(&rat!(1, 1)), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_rounding() {
+        let tests = [(7, 2), (-7, 2), (5, 3), (-5, 3), (4, 3), (-4, 3), (6, 1), (-6, 1), (0, 1),
+                     (i32::max_value(), 2), (-i32::max_value(), 2)];
+        for &(n, d) in tests.iter() {
+            let a = rat!(n, d);
+            let f = a.as_float();
+            assert_eq!(a.trunc().as_float(), f.trunc());
+            assert_eq!(a.floor().as_float(), f.floor());
+            assert_eq!(a.ceil().as_float(), f.ceil());
+            assert_eq!(a.round().as_float(), f.round());
+            assert_eq!(a.round_with_mode(RoundingMode::HalfAway), a.round());
+        }
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        use self::RoundingMode::*;
+        // (value, half-even, half-up, half-away)
+        let tests = [(rat!(5, 2), 2, 3, 3), (rat!(7, 2), 4, 4, 4), (rat!(-5, 2), -2, -2, -3),
+                     (rat!(-7, 2), -4, -3, -4), (rat!(1, 2), 0, 1, 1), (rat!(-1, 2), 0, 0, -1),
+                     (rat!(7, 3), 2, 2, 2), (rat!(-8, 3), -3, -3, -3), (rat!(4, 1), 4, 4, 4)];
+        for &(a, even, up, away) in tests.iter() {
+            assert_eq!(a.round_with_mode(HalfEven), rat!(even, 1), "{}", a);
+            assert_eq!(a.round_with_mode(HalfUp), rat!(up, 1), "{}", a);
+            assert_eq!(a.round_with_mode(HalfAway), rat!(away, 1), "{}", a);
+            let f = a.as_float();
+            assert_eq!(HalfEven.round_float(f), even as f64, "{}", f);
+            assert_eq!(HalfUp.round_float(f), up as f64, "{}", f);
+            assert_eq!(HalfAway.round_float(f), away as f64, "{}", f);
+        }
     }
 
     #[test]
@@ -446,6 +814,11 @@ mod tests {
                 compare(a.recip().unwrap(), b.recip().unwrap(), Ordering::Greater);
             }
         }
+        compare(rat!(1, 2147483647), rat!(2147483647, 1), Ordering::Less);
+        compare(rat!(2147483646, 2147483645), rat!(2147483647, 2147483646), Ordering::Greater);
+        compare(rat!(-2147483647, 2), rat!(1, 2147483647), Ordering::Less);
+        let max = i64::max_value();
+        compare(Rational { num: max - 1, den: max as u64 }, Rational { num: max, den: max as u64 - 1 }, Ordering::Less);
     }
 
     #[test]
@@ -469,4 +842,48 @@ mod tests {
         test_str(rat!(5, 2), "5/2");
         test_str(rat!(5, -2), "-5/2");
     }
+
+    #[test]
+    fn test_mixed_number() {
+        assert_eq!(rat!(7, 2).to_mixed_number(), (3, rat!(1, 2)));
+        assert_eq!(rat!(-7, 2).to_mixed_number(), (-3, rat!(-1, 2)));
+        assert_eq!(rat!(22, 7).to_mixed_number(), (3, rat!(1, 7)));
+        assert_eq!(rat!(1, 2).to_mixed_number(), (0, rat!(1, 2)));
+        assert_eq!(rat!(4, 1).to_mixed_number(), (4, rat!(0, 1)));
+        assert_eq!(rat!(7, 2).to_mixed_string(), "3 1/2");
+        assert_eq!(rat!(-7, 2).to_mixed_string(), "-3 1/2");
+        assert_eq!(rat!(-22, 7).to_mixed_string(), "-3 1/7");
+        // no whole part or no fraction
+        assert_eq!(rat!(1, 2).to_mixed_string(), "1/2");
+        assert_eq!(rat!(-1, 2).to_mixed_string(), "-1/2");
+        assert_eq!(rat!(4, 1).to_mixed_string(), "4");
+        assert_eq!(rat!(-4, 1).to_mixed_string(), "-4");
+        assert_eq!(rat!(0, 1).to_mixed_string(), "0");
+    }
+
+    #[test]
+    fn test_to_decimal() {
+        assert_eq!(rat!(7, 2).to_decimal(20), "3.5");
+        assert_eq!(rat!(-1, 8).to_decimal(20), "-0.125");
+        assert_eq!(rat!(42, 1).to_decimal(20), "42");
+        assert_eq!(rat!(1, 3).to_decimal(20), "0.(3)");
+        assert_eq!(rat!(1, 6).to_decimal(20), "0.1(6)");
+        assert_eq!(rat!(-22, 7).to_decimal(20), "-3.(142857)");
+        assert_eq!(rat!(1, 97).to_decimal(8), "0.01030927...");
+        assert_eq!(rat!(1, 1024).to_decimal(8), "0.00097656...");
+        assert_eq!(rat!(i32::max_value(), 2147483646).to_decimal(4), "1.0000...");
+    }
+
+    #[test]
+    fn test_to_scientific() {
+        assert_eq!(rat!(1500, 1).to_scientific(), Some("1.5e3".to_owned()));
+        assert_eq!(rat!(1000, 1).to_scientific(), Some("1e3".to_owned()));
+        assert_eq!(rat!(7, 2).to_scientific(), Some("3.5e0".to_owned()));
+        assert_eq!(rat!(-1, 8).to_scientific(), Some("-1.25e-1".to_owned()));
+        assert_eq!(rat!(1, 400).to_scientific(), Some("2.5e-3".to_owned()));
+        assert_eq!(rat!(0, 1).to_scientific(), Some("0e0".to_owned()));
+        assert_eq!(rat!(1, 1 << 30).to_scientific(), Some("9.31322574615478515625e-10".to_owned()));
+        assert_eq!(rat!(1, 3).to_scientific(), None);
+        assert_eq!(rat!(1, 6).to_scientific(), None);
+    }
 }
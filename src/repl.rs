@@ -0,0 +1,343 @@
+//! The read-eval-print loop. Input is read from any `BufRead` and output is written to any
+//! `Write`, so the loop can be tested without a terminal.
+
+use calc::Calculator;
+use units;
+use CalculatorError;
+
+use std::io;
+use std::io::{BufRead, Write};
+
+/// A REPL session
+pub struct Repl {
+    /// The calculator that evaluates each line
+    pub calc: Calculator,
+    /// The prompt printed before each line (changed with `prompt <string>`)
+    pub prompt: String,
+    /// Whether the code of each error (e.g. `E_DIVZERO`) is also printed, to the error output
+    /// if there is one (see `run_plain`) and otherwise to stderr
+    pub error_codes: bool,
+    /// Whether the result of a binding is printed with the variable's name (e.g.
+    /// `area = 50 m^2`)
+    pub echo_names: bool,
+}
+
+impl Repl {
+    /// Create a REPL using the given calculator
+    pub fn new(calc: Calculator) -> Repl {
+        Repl {
+            calc: calc,
+            prompt: "ucalc> ".to_owned(),
+            error_codes: false,
+            echo_names: false,
+        }
+    }
+    /// Read lines until "quit" or the end of the input, printing the result of each line.
+    /// A line ending in a semicolon is evaluated without printing its result.
+    /// `:frac`, `:dec`, `:mixed`, `:repeat`, and `:sci` change how exact results are displayed
+    /// (`:sci N` also rounds anything in scientific notation to N significant digits);
+    /// `:precision N` rounds other decimals to N digits after the point. These are short forms
+    /// of calculator commands (see `Calculator::command`). `:units` lists the units.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, output: &mut W) -> io::Result<()> {
+        loop {
+            let mut line = String::new();
+            try!(write!(output, "{}", self.prompt));
+            try!(output.flush());
+            if try!(input.read_line(&mut line)) == 0 {
+                // end of input
+                break
+            }
+            if line.trim() == "quit" { break }
+            let width = self.prompt.chars().count();
+            try!(self.line(line, output, Some(width)));
+        }
+        Ok(())
+    }
+    /// Run a script: like `run`, but without prompts. Errors are reported and the script
+    /// continues, unless `fail_fast` is set, in which case it stops at the first error.
+    /// Returns whether every line succeeded.
+    pub fn run_batch<R: BufRead, W: Write>(&mut self, input: R, output: &mut W, fail_fast: bool) -> io::Result<bool> {
+        let mut ok = true;
+        for line in input.lines() {
+            let line = try!(line);
+            if line.trim() == "quit" { break }
+            if !try!(self.line(line, output, None)) {
+                ok = false;
+                if fail_fast { break }
+            }
+        }
+        Ok(ok)
+    }
+    /// Run a script for another program (e.g. when the input is piped in): like `run_batch`,
+    /// but only the results are written to `output`, without `=> `. Errors and warnings are
+    /// written to `errors`.
+    pub fn run_plain<R: BufRead, W: Write, E: Write>(&mut self, input: R, output: &mut W, errors: &mut E, fail_fast: bool) -> io::Result<bool> {
+        let mut ok = true;
+        for line in input.lines() {
+            let line = try!(line);
+            if line.trim() == "quit" { break }
+            if !try!(self.respond(line, output, Some(&mut *errors), None)) {
+                ok = false;
+                if fail_fast { break }
+            }
+        }
+        Ok(ok)
+    }
+    /// Handle one line of input and print its output. Returns false if it was an error.
+    /// If the line was typed after a prompt of the given width, a caret is printed under a
+    /// syntax error.
+    pub fn line<W: Write>(&mut self, line: String, output: &mut W, prompt_width: Option<usize>) -> io::Result<bool> {
+        self.respond(line, output, None::<&mut io::Sink>, prompt_width)
+    }
+    /// Handle one line of input, writing errors and warnings to `errors` if it is given (and
+    /// then results without `=> `), or else everything to `output`
+    fn respond<W: Write, E: Write>(&mut self, mut line: String, output: &mut W, mut errors: Option<&mut E>, prompt_width: Option<usize>) -> io::Result<bool> {
+        // the prompt keeps everything after "prompt " (including trailing spaces)
+        if line.trim_left().starts_with("prompt ") {
+            self.prompt = line.trim_left()["prompt ".len()..].trim_right_matches(|c| c == '\n' || c == '\r').to_owned();
+            return Ok(true)
+        }
+        // lines starting with ':' change how results are displayed; they are never math
+        if line.trim() == ":units" {
+            try!(self.list_units(output));
+            return Ok(true)
+        }
+        if line.trim_left().starts_with(':') {
+            if let Some(Ok(_)) = self.calc.command(line.trim()) {
+                return Ok(true)
+            }
+            let usage = "usage: :frac | :dec | :mixed | :repeat | :sci [N] | :precision [N] | :units";
+            match errors {
+                Some(ref mut errors) => try!(writeln!(errors, "{}", usage)),
+                None => try!(writeln!(output, "{}", usage)),
+            }
+            return Ok(false)
+        }
+        // commands change settings
+        if let Some(res) = self.calc.command(line.trim()) {
+            match res {
+                Ok(ref msg) if msg.is_empty() => (),
+                Err(ref msg) if errors.is_some() => try!(writeln!(errors.as_mut().unwrap(), "{}", msg)),
+                Ok(ref msg) | Err(ref msg) => try!(writeln!(output, "{}", msg)),
+            }
+            return Ok(res.is_ok())
+        }
+        // a trailing semicolon suppresses the output
+        let suppress = line.trim_right().ends_with(';');
+        if suppress {
+            let end = line.trim_right().len() - 1;
+            line.truncate(end);
+        }
+        let res = self.calc.run(&line);
+        let marker = if errors.is_some() { "" } else { "=> " };
+        match res {
+            Ok(_) if suppress => (),
+            Ok(val) => match self.calc.binding {
+                Some(ref name) if self.echo_names => try!(writeln!(output, "{}{} = {}", marker, name, self.calc.format(&line, &val))),
+                _ => try!(writeln!(output, "{}{}", marker, self.calc.format(&line, &val))),
+            },
+            Err(e) if errors.is_some() => try!(writeln!(errors.as_mut().unwrap(), "{}", e)),
+            Err(e @ CalculatorError::SyntaxError) => {
+                if let (Some(width), Some(pos)) = (prompt_width, self.calc.error_position) {
+                    let column = line[..pos].chars().count();
+                    try!(writeln!(output, "{}^", " ".repeat(width + column)));
+                }
+                try!(writeln!(output, "{}", e))
+            },
+            Err(e) => try!(writeln!(output, "=> {}", e)),
+        }
+        if let Err(e) = res {
+            if self.error_codes {
+                match errors {
+                    Some(ref mut errors) => try!(writeln!(errors, "{}", e.code())),
+                    None => try!(writeln!(io::stderr(), "{}", e.code())),
+                }
+            }
+        }
+        for w in &self.calc.warnings {
+            match errors {
+                Some(ref mut errors) => try!(writeln!(errors, "warning: {}", w)),
+                None => try!(writeln!(output, "warning: {}", w)),
+            }
+        }
+        Ok(res.is_ok())
+    }
+    /// Print every unit and its value in SI base units, grouped by dimension
+    fn list_units<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let mut dimension = None;
+        for (name, value) in units::all() {
+            if dimension != Some(value.unit) {
+                dimension = Some(value.unit);
+                let base = value.unit.to_string();
+                try!(writeln!(output, "{}:", if base.is_empty() { "unitless" } else { &base[..] }));
+            }
+            try!(writeln!(output, "  {} = {}", name, value.value.format(self.calc.display)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calc::Calculator;
+
+    /// Run the REPL on some input and return the output
+    fn run_repl(input: &str) -> String {
+        run_repl_with(Repl::new(Calculator::new()), input)
+    }
+
+    /// Run a given REPL on some input and return the output
+    fn run_repl_with(mut repl: Repl, input: &str) -> String {
+        let mut output = Vec::new();
+        repl.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_repl() {
+        assert_eq!(run_repl("1 + 1\n"), "ucalc> => 2\nucalc> ");
+        // the caret is under the error, after the prompt and the input on the line above
+        assert_eq!(run_repl("1 +\nquit\n1\n"), "ucalc>           ^\nsyntax error\nucalc> ");
+        assert_eq!(run_repl("2 * (3 + 4))\n"), "ucalc>                   ^\nsyntax error\nucalc> ");
+        assert_eq!(run_repl("undefined\n"), "ucalc> syntax error\nucalc> ");
+        assert_eq!(run_repl("1/0\n"), "ucalc> => division by zero\nucalc> ");
+        assert_eq!(run_repl("calcmode integer\n7/2"), "ucalc> ucalc> => 3\nucalc> ");
+        assert_eq!(run_repl("65536(65536)(65536)(65536)\n"), "ucalc> => 1.8446744073709552e19\nwarning: a multiplication overflowed, so the result is inexact\nucalc> ");
+        assert_eq!(run_repl("d := 3 mi\nd\nd/2\n"), "ucalc> => 3 mi\nucalc> => 3 mi\nucalc> => 301752/125 m\nucalc> ");
+    }
+
+    #[test]
+    fn test_raw() {
+        assert_eq!(run_repl("3 * 4\nraw\n"), "ucalc> => 12\nucalc> 12\nucalc> ");
+        assert_eq!(run_repl("1 mi / (2 hr)\nraw\n"), "ucalc> => 11/15 ft/s\nucalc> 1397/6250\nucalc> ");
+        assert_eq!(run_repl(":dec\n7/2 m\nraw\n"), "ucalc> ucalc> => 3.5 m\nucalc> 3.5\nucalc> ");
+        assert_eq!(run_repl("raw\n"), "ucalc> no result yet\nucalc> ");
+    }
+
+    #[test]
+    fn test_echo_names() {
+        let mut repl = Repl::new(Calculator::new());
+        repl.echo_names = true;
+        assert_eq!(run_repl_with(repl, "area = 50 m^2
+area * 2
+d := 3 mi
+x = 1;
+"),
+                   "ucalc> => area = 50 m^2
+ucalc> => 100 m^2
+ucalc> => d = 3 mi
+ucalc> ucalc> ");
+        // off by default
+        assert_eq!(run_repl("area = 50 m^2
+"), "ucalc> => 50 m^2
+ucalc> ");
+    }
+
+    #[test]
+    fn test_error_codes() {
+        /// Run piped input and return the output and the error output
+        fn run_plain(repl: &mut Repl, input: &str) -> (String, String) {
+            let (mut output, mut errors) = (Vec::new(), Vec::new());
+            repl.run_plain(input.as_bytes(), &mut output, &mut errors, false).unwrap();
+            (String::from_utf8(output).unwrap(), String::from_utf8(errors).unwrap())
+        }
+        let mut repl = Repl::new(Calculator::new());
+        repl.error_codes = true;
+        assert_eq!(run_plain(&mut repl, "1/0\n2 + 2\n1 m + 1 s\n1 +\n"),
+                   ("4\n".to_owned(), "division by zero\nE_DIVZERO\nincompatible units\nE_UNIT\nsyntax error\nE_SYNTAX\n".to_owned()));
+        repl.error_codes = false;
+        assert_eq!(run_plain(&mut repl, "1/0\n"), (String::new(), "division by zero\n".to_owned()));
+    }
+
+    #[test]
+    fn test_suppress_output() {
+        assert_eq!(run_repl("2 + 3;\n"), "ucalc> ucalc> ");
+        assert_eq!(run_repl("2 + 3 ; \n2 + 3\n"), "ucalc> ucalc> => 5\nucalc> ");
+        // the result is still calculated
+        assert_eq!(run_repl("1/65536/65536/65536/65536;\nwhy-inexact\n"), "ucalc> ucalc> an exact calculation overflowed\nucalc> ");
+        // errors are not suppressed
+        assert_eq!(run_repl("1/0;\n"), "ucalc> => division by zero\nucalc> ");
+        assert_eq!(run_repl(";\n"), "ucalc>        ^\nsyntax error\nucalc> ");
+    }
+
+    #[test]
+    fn test_display_commands() {
+        assert_eq!(run_repl("7/2\n:dec\n7/2\n:mixed\n7/2\n-7/2 m\n:frac\n7/2\n"),
+                   "ucalc> => 7/2\nucalc> ucalc> => 3.5\nucalc> ucalc> => 3 1/2\nucalc> => -3 1/2 m\nucalc> ucalc> => 7/2\nucalc> ");
+        assert_eq!(run_repl(":repeat\n1/6\n"), "ucalc> ucalc> => 0.1(6)\nucalc> ");
+        assert_eq!(run_repl(":sci\n1500 m/s\n"), "ucalc> ucalc> => 1.5e3 m/s\nucalc> ");
+        // with a number of digits, results in scientific notation are rounded
+        assert_eq!(run_repl(":sci 3\n1500 m/s\n1/3\n:dec\n2^60 * 1.0\n:sci\n1/3\n"),
+                   "ucalc> ucalc> => 1.50e3 m/s\nucalc> => 3.33e-1\nucalc> ucalc> => 1.15e18\nucalc> ucalc> => 1/3\nucalc> ");
+        assert_eq!(run_repl("pi\n:precision 4\npi\n1/3\n:dec\n1/3\n:precision\npi\n"),
+                   "ucalc> => 3.141592653589793\nucalc> ucalc> => 3.1416\nucalc> => 1/3\nucalc> ucalc> => 0.3333\nucalc> ucalc> => 3.141592653589793\nucalc> ");
+        assert_eq!(run_repl(":precision 2\n1 lb in kg\n"), "ucalc> ucalc> => 0.45 kg\nucalc> ");
+        assert_eq!(run_repl(":sci 0\n"), "ucalc> usage: :frac | :dec | :mixed | :repeat | :sci [N] | :precision [N] | :units\nucalc> ");
+        assert_eq!(run_repl(":fraction\n"), "ucalc> usage: :frac | :dec | :mixed | :repeat | :sci [N] | :precision [N] | :units\nucalc> ");
+        assert_eq!(run_repl(":\n"), "ucalc> usage: :frac | :dec | :mixed | :repeat | :sci [N] | :precision [N] | :units\nucalc> ");
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        /// A reader that always fails
+        struct Broken;
+        impl io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "broken"))
+            }
+        }
+        // the end of the input ends the loop, even in the middle of a line
+        assert_eq!(run_repl(""), "ucalc> ");
+        assert_eq!(run_repl("1 + 1"), "ucalc> => 2\nucalc> ");
+        // errors are returned rather than panicking
+        let mut output = Vec::new();
+        assert!(Repl::new(Calculator::new()).run(io::BufReader::new(Broken), &mut output).is_err());
+        assert!(Repl::new(Calculator::new()).run(&b"1 + 1\n\xff\n"[..], &mut output).is_err());
+        assert!(Repl::new(Calculator::new()).run_batch(io::BufReader::new(Broken), &mut output, false).is_err());
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut repl = Repl::new(Calculator::new());
+        repl.prompt = "> ".to_owned();
+        assert_eq!(run_repl_with(repl, "1 + 1\n"), "> => 2\n> ");
+        assert_eq!(run_repl("prompt calc: \n2\n"), "ucalc> calc: => 2\ncalc: ");
+    }
+
+    #[test]
+    fn test_batch() {
+        /// Run a script and return whether it succeeded and its output
+        fn run_batch(input: &str, fail_fast: bool) -> (bool, String) {
+            let mut output = Vec::new();
+            let ok = Repl::new(Calculator::new()).run_batch(input.as_bytes(), &mut output, fail_fast).unwrap();
+            (ok, String::from_utf8(output).unwrap())
+        }
+        assert_eq!(run_batch("1 + 1\n2 + 2\n", false), (true, "=> 2\n=> 4\n".to_owned()));
+        assert_eq!(run_batch("1 + 1\n1/0\n2 + 2\n", false), (false, "=> 2\n=> division by zero\n=> 4\n".to_owned()));
+        assert_eq!(run_batch("1 + 1\n1/0\n2 + 2\n", true), (false, "=> 2\n=> division by zero\n".to_owned()));
+        assert_eq!(run_batch("maxden 0\n2 + 2\n", true), (false, "usage: maxden N|off\n".to_owned()));
+    }
+
+    #[test]
+    fn test_units() {
+        let listing = run_repl(":units\n");
+        assert!(listing.contains("\nm:\n  ft = 381/1250\n"), "{}", listing);
+        assert!(listing.contains("\n  m = 1\n"), "{}", listing);
+        assert!(listing.contains("\nN:\n  N = 1\n"), "{}", listing);
+        assert!(listing.contains("unitless:\n"), "{}", listing);
+        assert_eq!(run_repl(":unit\n"), "ucalc> usage: :frac | :dec | :mixed | :repeat | :sci [N] | :precision [N] | :units\nucalc> ");
+    }
+
+    #[test]
+    fn test_plain() {
+        let mut repl = Repl::new(Calculator::new());
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        // errors and warnings are written separately, so only the results are in the output
+        let input = "3*4\n1/0\n1 +\nx := 5 km\nx in m\n65536(65536)(65536)(65536)\n";
+        assert!(!repl.run_plain(input.as_bytes(), &mut output, &mut errors, false).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "12\n5 km\n5000 m\n1.8446744073709552e19\n");
+        assert_eq!(String::from_utf8(errors).unwrap(),
+                   "division by zero\nsyntax error\nwarning: a multiplication overflowed, so the result is inexact\n");
+    }
+}
@@ -0,0 +1,145 @@
+//! Significant figures. A number as written has a number of significant figures (e.g. 3.00
+//! has 3); multiplication and division keep the fewest significant figures of the operands,
+//! and addition and subtraction keep the fewest decimal places.
+
+use rational::RoundingMode;
+
+/// The precision of a value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SigFigs {
+    /// The number of significant figures (at least 1)
+    pub figures: i32,
+    /// The decimal place of the last significant figure (e.g. 2 for 1.25, -2 for 1200)
+    pub decimals: i32,
+}
+
+/// The number of digits to the left of the decimal point (e.g. 3 for 123.4, -1 for 0.012)
+fn magnitude(value: f64) -> i32 {
+    if value == 0.0 { 0 } else { value.abs().log10().floor() as i32 + 1 }
+}
+
+impl SigFigs {
+    /// The precision of a number as written (e.g. `2.50`, `1200`, or `1.5e3`). Trailing zeros
+    /// of an integer are not significant.
+    pub fn of_literal(literal: &str) -> SigFigs {
+        let mut parts = literal.splitn(2, |c| c == 'e' || c == 'E');
+        let mantissa = parts.next().unwrap_or("");
+        let exponent: i32 = parts.next().and_then(|e| e.trim_left_matches('+').parse().ok()).unwrap_or(0);
+        let (int, frac) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+            None => (mantissa, None),
+        };
+        let digits = format!("{}{}", int, frac.unwrap_or(""));
+        let digits = digits.trim_left_matches('0');
+        let (figures, decimals) = match frac {
+            Some(frac) => (digits.len() as i32, frac.len() as i32),
+            None => {
+                let trimmed = digits.trim_right_matches('0');
+                (trimmed.len() as i32, trimmed.len() as i32 - digits.len() as i32)
+            },
+        };
+        SigFigs { figures: if figures > 0 { figures } else { 1 }, decimals: decimals - exponent }
+    }
+    /// A value with the given number of significant figures
+    pub fn with_figures(value: f64, figures: i32) -> SigFigs {
+        SigFigs { figures: figures, decimals: figures - magnitude(value) }
+    }
+    /// A value whose last significant figure is in the given decimal place
+    pub fn with_decimals(value: f64, decimals: i32) -> SigFigs {
+        let figures = magnitude(value) + decimals;
+        SigFigs { figures: if figures > 0 { figures } else { 1 }, decimals: decimals }
+    }
+}
+
+/// The precision of a product or quotient with the given result. Exact operands (None) do
+/// not limit the precision.
+pub fn product(a: Option<SigFigs>, b: Option<SigFigs>, result: f64) -> Option<SigFigs> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(SigFigs::with_figures(result, a.figures.min(b.figures))),
+        (Some(a), None) | (None, Some(a)) => Some(SigFigs::with_figures(result, a.figures)),
+        (None, None) => None,
+    }
+}
+
+/// The precision of a sum or difference with the given result. Exact operands (None) do not
+/// limit the precision.
+pub fn sum(a: Option<SigFigs>, b: Option<SigFigs>, result: f64) -> Option<SigFigs> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(SigFigs::with_decimals(result, a.decimals.min(b.decimals))),
+        (Some(a), None) | (None, Some(a)) => Some(SigFigs::with_decimals(result, a.decimals)),
+        (None, None) => None,
+    }
+}
+
+/// Format a value rounded to the given number of significant figures, with halfway cases
+/// rounded according to `mode`
+pub fn round(value: f64, figures: i32, mode: RoundingMode) -> String {
+    let figures = if figures > 0 { figures } else { 1 };
+    // round to the last significant digit; scaling by a power of 10 (rather than its
+    // reciprocal) keeps e.g. 1234 / 100 from picking up an error
+    let decimals = figures - magnitude(value);
+    let scale = 10f64.powi(decimals.abs());
+    let rounded = if decimals >= 0 {
+        mode.round_float(value * scale) / scale
+    } else {
+        mode.round_float(value / scale) * scale
+    };
+    // rounding can change the magnitude (9.96 -> 10)
+    let decimals = figures - magnitude(rounded);
+    if decimals > 0 {
+        format!("{:.*}", decimals as usize, rounded)
+    } else {
+        format!("{:.0}", rounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of_literal() {
+        assert_eq!(SigFigs::of_literal("2.5"), SigFigs { figures: 2, decimals: 1 });
+        assert_eq!(SigFigs::of_literal("3.00"), SigFigs { figures: 3, decimals: 2 });
+        assert_eq!(SigFigs::of_literal("0.012"), SigFigs { figures: 2, decimals: 3 });
+        assert_eq!(SigFigs::of_literal("1200"), SigFigs { figures: 2, decimals: -2 });
+        assert_eq!(SigFigs::of_literal("1200."), SigFigs { figures: 4, decimals: 0 });
+        assert_eq!(SigFigs::of_literal("1.50e3"), SigFigs { figures: 3, decimals: -1 });
+        assert_eq!(SigFigs::of_literal("5E-3"), SigFigs { figures: 1, decimals: 3 });
+        assert_eq!(SigFigs::of_literal("0"), SigFigs { figures: 1, decimals: 0 });
+    }
+
+    #[test]
+    fn test_rules() {
+        let a = SigFigs::of_literal("2.5");
+        let b = SigFigs::of_literal("3.00");
+        assert_eq!(product(Some(a), Some(b), 7.5), Some(SigFigs { figures: 2, decimals: 1 }));
+        assert_eq!(product(Some(b), None, 300.0), Some(SigFigs { figures: 3, decimals: 0 }));
+        assert_eq!(product(None, None, 7.5), None);
+        let c = SigFigs::of_literal("12.11");
+        let d = SigFigs::of_literal("0.3");
+        assert_eq!(sum(Some(c), Some(d), 12.41), Some(SigFigs { figures: 3, decimals: 1 }));
+        assert_eq!(sum(Some(c), Some(c), 0.0), Some(SigFigs { figures: 2, decimals: 2 }));
+    }
+
+    #[test]
+    fn test_round() {
+        let even = RoundingMode::HalfEven;
+        assert_eq!(round(7.5, 2, even), "7.5");
+        assert_eq!(round(12.41, 3, even), "12.4");
+        assert_eq!(round(9.96, 2, even), "10");
+        assert_eq!(round(1234.0, 2, even), "1200");
+        assert_eq!(round(0.0123456, 3, even), "0.0123");
+        assert_eq!(round(-2.0, 3, even), "-2.00");
+        assert_eq!(round(0.0, 2, even), "0.00");
+        // halfway cases
+        assert_eq!(round(2.5, 1, even), "2");
+        assert_eq!(round(3.5, 1, even), "4");
+        assert_eq!(round(0.125, 2, even), "0.12");
+        assert_eq!(round(2.5, 1, RoundingMode::HalfUp), "3");
+        assert_eq!(round(-2.5, 1, RoundingMode::HalfUp), "-2");
+        assert_eq!(round(-2.5, 1, RoundingMode::HalfAway), "-3");
+        assert_eq!(round(1250.0, 2, RoundingMode::HalfAway), "1300");
+        assert_eq!(round(1250.0, 2, even), "1200");
+    }
+}
@@ -5,7 +5,10 @@ use rational::*;
 use std::ops::{Add,Sub,Mul,Neg};
 
 /// A unit struct, representing unit dimensions.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+///
+/// Units are ordered by comparing the exponents in the order of the fields below. This order
+/// has no physical meaning, but it is useful for sorting.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Unit {
     /// exponent of meters
     pub m: Rational,
@@ -21,6 +24,8 @@ pub struct Unit {
     pub cd: Rational,
     /// exponent of moles
     pub mol: Rational,
+    /// exponent of bytes (not an SI unit, but data sizes are often converted)
+    pub b: Rational,
 }
 
 impl Unit {
@@ -34,6 +39,7 @@ impl Unit {
             k: Rational::zero(),
             cd: Rational::zero(),
             mol: Rational::zero(),
+            b: Rational::zero(),
         }
     }
     // may overflow
@@ -47,6 +53,7 @@ impl Unit {
             k: try!(self.k.add(&other.k)),
             cd: try!(self.cd.add(&other.cd)),
             mol: try!(self.mol.add(&other.mol)),
+            b: try!(self.b.add(&other.b)),
         })
     }
     /// subtract two units (corresponds to division of values)
@@ -59,8 +66,15 @@ impl Unit {
             k: try!(self.k.sub(&other.k)),
             cd: try!(self.cd.sub(&other.cd)),
             mol: try!(self.mol.sub(&other.mol)),
+            b: try!(self.b.sub(&other.b)),
         })
     }
+    /// the total degree: the sum of the absolute values of the exponents (e.g. 3 for m/s^2)
+    pub fn degree(&self) -> f64 {
+        [self.m, self.kg, self.s, self.a, self.k, self.cd, self.mol, self.b].iter()
+            .map(|e| e.as_float().abs())
+            .sum()
+    }
     /// multiply a unit by a scalar (corresponds to exponentiation)
     pub fn mul(&self, other: &Rational) -> Result<Unit, OverflowError> {
         Ok(Unit {
@@ -71,6 +85,7 @@ impl Unit {
             k: try!(self.k.mul(&other)),
             cd: try!(self.cd.mul(&other)),
             mol: try!(self.mol.mul(&other)),
+            b: try!(self.b.mul(&other)),
         })
     }
 }
@@ -109,6 +124,7 @@ impl Neg for Unit {
             k: -self.k,
             cd: -self.cd,
             mol: -self.mol,
+            b: -self.b,
         }
     }
 }
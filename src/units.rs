@@ -2,49 +2,49 @@
 
 use unit::Unit;
 use uval::UnitValue;
-use value::Value;
+use value::{Value, InexactReason};
 use rational::Rational;
 
 use std::fmt;
-use std::fmt::Write;
 
 use phf;
 
 // here are some types of units
 // I'm only doing the common units here; it'd take a while to type out all the uncommon ones
-const DIMENSIONLESS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const LENGTH: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const MASS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const TIME: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const CURRENT: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const TEMPERATURE: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const INTENSITY: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 1, den: 1}, mol: Rational {num: 0, den: 1}};
-const AMOUNT: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 1, den: 1}};
-const FREQUENCY: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const FORCE: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const PRESSURE: Unit = Unit {m: Rational {num: -1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const ENERGY: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const POWER: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const CHARGE: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const VOLTAGE: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: -1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const CAPACITANCE: Unit = Unit {m: Rational {num: -2, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: 4, den: 1}, a: Rational {num: 2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const RESISTANCE: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const MAG_FIELD: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: -1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-
-const AREA: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const VOLUME: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-
-const C_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const GC_UNITS: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const H_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const MU0_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const EP0_UNITS: Unit = Unit {m: Rational {num: -3, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: 4, den: 1}, a: Rational {num: 2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const KE_UNITS: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -4, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const NA_UNITS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}};
-const KB_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: -1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
-const F_UNITS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}};
-const R_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: -1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}};
-const G_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}};
+const DIMENSIONLESS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const LENGTH: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const MASS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const TIME: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const CURRENT: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const TEMPERATURE: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const INTENSITY: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 1, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const AMOUNT: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 1, den: 1}, b: Rational {num: 0, den: 1}};
+const INFORMATION: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 1, den: 1}};
+const FREQUENCY: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const FORCE: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const PRESSURE: Unit = Unit {m: Rational {num: -1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const ENERGY: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const POWER: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const CHARGE: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const VOLTAGE: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: -1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const CAPACITANCE: Unit = Unit {m: Rational {num: -2, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: 4, den: 1}, a: Rational {num: 2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const RESISTANCE: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -3, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const MAG_FIELD: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: -1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+
+const AREA: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const VOLUME: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+
+const C_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const GC_UNITS: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const H_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -1, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const MU0_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const EP0_UNITS: Unit = Unit {m: Rational {num: -3, den: 1}, kg: Rational {num: -1, den: 1}, s: Rational {num: 4, den: 1}, a: Rational {num: 2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const KE_UNITS: Unit = Unit {m: Rational {num: 3, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -4, den: 1}, a: Rational {num: -2, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const NA_UNITS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 0, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}, b: Rational {num: 0, den: 1}};
+const KB_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: -1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
+const F_UNITS: Unit = Unit {m: Rational {num: 0, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: 1, den: 1}, a: Rational {num: 1, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}, b: Rational {num: 0, den: 1}};
+const R_UNITS: Unit = Unit {m: Rational {num: 2, den: 1}, kg: Rational {num: 1, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: -1, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: -1, den: 1}, b: Rational {num: 0, den: 1}};
+const G_UNITS: Unit = Unit {m: Rational {num: 1, den: 1}, kg: Rational {num: 0, den: 1}, s: Rational {num: -2, den: 1}, a: Rational {num: 0, den: 1}, k: Rational {num: 0, den: 1}, cd: Rational {num: 0, den: 1}, mol: Rational {num: 0, den: 1}, b: Rational {num: 0, den: 1}};
 
 // one (used for SI derived/base units)
 const ONE: Value = Value::Exact(Rational {num: 1, den: 1});
@@ -52,7 +52,7 @@ const ONE: Value = Value::Exact(Rational {num: 1, den: 1});
 // shortcut for values
 macro_rules! num {
     (E $a:expr, $b:expr) => (Value::Exact(Rational {num: $a, den: $b}));
-    (I $a:expr) => (Value::Inexact($a));
+    (I $a:expr) => (Value::Inexact($a, InexactReason::ExplicitFloat));
 }
 
 /// Lookup table for named units
@@ -113,19 +113,30 @@ static UNITS: phf::Map<&'static str, UnitValue> = phf_map! {
     "tonne" => UnitValue {unit: MASS, value: num!(E 1000,1)},
     "L" => UnitValue {unit: VOLUME, value: num!(E 1,1000)},
     "ml" => UnitValue {unit: VOLUME, value: num!(E 1,1000000)},
-    "mL" => UnitValue {unit: VOLUME, value: num!(E 1,1000000)},
-    "cm" => UnitValue {unit: LENGTH, value: num!(E 1,100)},
-    "mm" => UnitValue {unit: LENGTH, value: num!(E 1,1000)},
-    "km" => UnitValue {unit: LENGTH, value: num!(E 1000,1)},
     "atm" => UnitValue {unit: PRESSURE, value: num!(I 101325.0)},
     "bar" => UnitValue {unit: PRESSURE, value: num!(E 100000,1)},
+    // temperature differences (converting a temperature with `to` also uses the zero points
+    // below, so e.g. 100 degC to degF is 212 degF, but 100 degC + 100 degC is 200 K)
+    "degC" => UnitValue {unit: TEMPERATURE, value: ONE},
+    "degF" => UnitValue {unit: TEMPERATURE, value: num!(E 5,9)},
     // degrees
     "deg" => UnitValue {unit: DIMENSIONLESS, value: num!(I 0.0174532925199432957)},
     // time
     "min" => UnitValue {unit: TIME, value: num!(E 60,1)},
+    "h" => UnitValue {unit: TIME, value: num!(E 3600,1)},
     "hr" => UnitValue {unit: TIME, value: num!(E 3600,1)},
     "day" => UnitValue {unit: TIME, value: num!(E 86400,1)},
     "amu" => UnitValue {unit: MASS, value: num!(I 1.66053892173e-27)}, // actually u, but u is easy to mistype
+    // data (the SI prefixes are decimal, e.g. kB is 1000 B; the binary ones are listed here)
+    "B" => UnitValue {unit: INFORMATION, value: ONE},
+    "byte" => UnitValue {unit: INFORMATION, value: ONE},
+    "bytes" => UnitValue {unit: INFORMATION, value: ONE},
+    "bit" => UnitValue {unit: INFORMATION, value: num!(E 1,8)},
+    "bits" => UnitValue {unit: INFORMATION, value: num!(E 1,8)},
+    "KiB" => UnitValue {unit: INFORMATION, value: num!(E 1024,1)},
+    "MiB" => UnitValue {unit: INFORMATION, value: num!(E 1048576,1)},
+    "GiB" => UnitValue {unit: INFORMATION, value: num!(E 1073741824,1)},
+    "TiB" => UnitValue {unit: INFORMATION, value: num!(E 1099511627776,1)},
     // CONSTANTS
     "_c" => UnitValue {unit: C_UNITS, value: num!(E 299792458,1)},
     "_G" => UnitValue {unit: GC_UNITS, value: num!(I 6.6740831e-11)},
@@ -153,17 +164,18 @@ static UNITS: phf::Map<&'static str, UnitValue> = phf_map! {
 // * K
 // * cd
 // * mol
+// (and bytes in the top digit)
 //
 // 0-9 are positive, and
 // A-F are -1 through -6
 // Look up a unit's exponents to find a derived unit
 static LOOKUP: phf::Map<u32, &'static str> = phf_map! {
-//  0x0mksAKcm
+//  0xBmksAKcm
     0x000A0000u32 => "Hz",
     0x011B0000u32 => "N",
     0x0A1B0000u32 => "Pa",
     0x021B0000u32 => "J",
-    0x021C0000u32 => "J",
+    0x021C0000u32 => "W",
     0x00011000u32 => "C",
     0x021CA000u32 => "V",
     0x0BA42000u32 => "F",
@@ -173,7 +185,102 @@ static LOOKUP: phf::Map<u32, &'static str> = phf_map! {
 
 /// Look up a name to find the corresponding unit
 pub fn get(key: &str) -> Option<UnitValue> {
-    UNITS.get(key).cloned()
+    // a registered unit is preferred over a prefixed one (e.g. min is minutes, not milli-inches)
+    UNITS.get(key).cloned().or_else(|| parse_prefixed(key))
+}
+
+/// Every named unit and constant with its value in SI base units, sorted by dimension and then
+/// by name (prefixed units are not included)
+pub fn all() -> Vec<(&'static str, UnitValue)> {
+    let mut all: Vec<_> = UNITS.entries().map(|(&name, &value)| (name, value)).collect();
+    all.sort_by(|a, b| a.1.unit.cmp(&b.1.unit).then(a.0.cmp(b.0)));
+    all
+}
+
+/// SI prefixes and their factors (exact where they fit in a Rational).
+/// `da` comes before `d` so that it is tried first.
+static PREFIXES: [(&'static str, Value); 21] = [
+    ("Y", num!(I 1e24)), ("Z", num!(I 1e21)), ("E", num!(I 1e18)), ("P", num!(I 1e15)),
+    ("T", num!(I 1e12)), ("G", num!(E 1000000000,1)), ("M", num!(E 1000000,1)),
+    ("k", num!(E 1000,1)), ("h", num!(E 100,1)), ("da", num!(E 10,1)), ("d", num!(E 1,10)),
+    ("c", num!(E 1,100)), ("m", num!(E 1,1000)), ("u", num!(E 1,1000000)), ("µ", num!(E 1,1000000)),
+    ("n", num!(E 1,1000000000)), ("p", num!(I 1e-12)), ("f", num!(I 1e-15)), ("a", num!(I 1e-18)),
+    ("z", num!(I 1e-21)), ("y", num!(I 1e-24)),
+];
+
+/// Look up a unit with an SI prefix (e.g. km is 1000 m). Constants can't be prefixed.
+pub fn parse_prefixed(key: &str) -> Option<UnitValue> {
+    PREFIXES.iter()
+        .filter(|&&(p, _)| key.starts_with(p) && key.len() > p.len() && !key[p.len()..].starts_with('_'))
+        .filter_map(|&(p, ref factor)| UNITS.get(&key[p.len()..])
+                    .and_then(|base| (&base.value).mul(factor).ok().map(|v| UnitValue {unit: base.unit, value: v})))
+        .next()
+}
+
+/// The unit systems that named units belong to
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum System {
+    /// SI and other metric units (e.g. m, kg, L); units and constants that belong to no
+    /// system also count as metric
+    Metric,
+    /// US customary units (e.g. ft, lb, gal)
+    Customary,
+}
+
+/// The units of the US customary system
+static CUSTOMARY: [&'static str; 19] = [
+    "in", "ft", "yd", "mi", "rd", "fur", "lea", "ac", "acre", "gal", "qt", "pt", "cup", "floz",
+    "tbsp", "tsp", "lb", "oz", "ton",
+];
+
+/// The system a unit belongs to
+pub fn system(key: &str) -> System {
+    if CUSTOMARY.contains(&key) { System::Customary } else { System::Metric }
+}
+
+/// The system of a unit expression as it was written (e.g. `ft/s^2`): that of its first unit
+pub fn written_system(text: &str) -> System {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .find(|name| !name.is_empty())
+        .map_or(System::Metric, system)
+}
+
+/// Write a unit in the base units of a system, so that a result can be shown in the system
+/// it was written in. Customary lengths are in feet and masses in pounds (e.g. `ft^2` or
+/// `lb*ft/s^2`); other dimensions keep their SI units. Returns the unit as written and its
+/// value, or None if the unit is already written that way (all metric units are).
+pub fn simplify_units(unit: &Unit, system: System) -> Option<(String, UnitValue)> {
+    if system == System::Metric || (unit.m.is_zero() && unit.kg.is_zero()) {
+        return None
+    }
+    // e.g. ft^2 lb is (381/1250)^2 * 0.45359237 m^2 kg
+    let mut scale = UnitValue::with_unit(ONE, Unit::zero());
+    for &(name, e) in &[("ft", unit.m), ("lb", unit.kg)] {
+        if !e.is_zero() {
+            let power = get(name).and_then(|u| u.pow(&UnitValue::with_unit(Value::Exact(e), Unit::zero())).ok());
+            scale = match power.and_then(|p| scale.mul(&p).ok()) {
+                Some(scale) => scale,
+                None => return None,
+            };
+        }
+    }
+    Some((write_unit(unit, ["lb", "ft", "s", "A", "K", "mol", "cd", "B"]), UnitValue { value: scale.value, unit: *unit }))
+}
+
+/// Temperature scales and the temperature of their zero point, in kelvins
+static TEMPERATURE_ZEROS: [(&'static str, Value); 3] = [
+    ("K", num!(E 0,1)), ("degC", num!(E 5463,20)), ("degF", num!(E 45967,180)),
+];
+
+/// The zero point of a temperature scale in kelvins (e.g. 273.15 for degC), or None if the
+/// name is not a temperature unit
+pub fn temperature_zero(key: &str) -> Option<Value> {
+    TEMPERATURE_ZEROS.iter().find(|&&(name, _)| name == key).map(|&(_, zero)| zero)
+}
+
+/// Get the scale factor of a unit: its value in SI base units (e.g. 1000 for km)
+pub fn scale(key: &str) -> Option<Value> {
+    get(key).map(|a| a.value)
 }
 
 /// turn an exponent into the hex scheme above
@@ -196,6 +303,7 @@ fn u_hash(u: &Unit) -> Result<u32, ()> {
         (try!(as_int(&u.k  )) as u32) << 8  |
         (try!(as_int(&u.cd )) as u32) << 4  |
         (try!(as_int(&u.mol)) as u32) << 0  |
+        (try!(as_int(&u.b  )) as u32) << 28 |
         0
     )
 }
@@ -205,45 +313,61 @@ macro_rules! fmt_unit {
     ($u:expr, $name:expr, $num:ident, $den:ident) => {
         if !$u.is_negative() {
             if $u.is_one() {
-                $num.push_str($name);
-                $num.push(' ');
+                $num.push($name.to_owned());
             } else if !$u.is_zero() {
-                write!($num, "{}^{} ", $name, $u).unwrap();
+                $num.push(format!("{}^{}", $name, exponent($u)));
             }
         } else {
             let neg = -$u;
             if neg.is_one() {
-                $den.push_str($name);
-                $den.push(' ');
+                $den.push($name.to_owned());
             } else if !neg.is_zero() {
-                write!($den, "{}^{} ", $name, neg).unwrap();
+                $den.push(format!("{}^{}", $name, exponent(neg)));
             }
         }
     }
 }
 
+/// An exponent, in parentheses if it is a fraction (e.g. `(1/2)` so that `m^(1/2)` is not
+/// read as `m^1 / 2`)
+fn exponent(e: Rational) -> String {
+    if e.is_integer() { format!("{}", e) } else { format!("({})", e) }
+}
+
+/// Write a unit with the given names for the mass, length, time, current, temperature,
+/// amount, intensity and data dimensions, without spaces so that it reads as one unit after
+/// a number (e.g. `kg*m/s^2`, `kg/(s^2*A)` or `s^-1`)
+fn write_unit(unit: &Unit, names: [&str; 8]) -> String {
+    let mut num = Vec::new();
+    let mut den = Vec::new();
+    fmt_unit!(unit.kg, names[0], num, den);
+    fmt_unit!(unit.m, names[1], num, den);
+    fmt_unit!(unit.s, names[2], num, den);
+    fmt_unit!(unit.a, names[3], num, den);
+    fmt_unit!(unit.k, names[4], num, den);
+    fmt_unit!(unit.mol, names[5], num, den);
+    fmt_unit!(unit.cd, names[6], num, den);
+    fmt_unit!(unit.b, names[7], num, den);
+    match (num.len(), den.len()) {
+        (_, 0) => num.join("*"),
+        // with no numerator, the exponents are negative
+        (0, _) => den.iter().map(|d| match d.find('^') {
+            Some(i) if d[i + 1..].starts_with('(') => format!("{}^(-{}", &d[..i], &d[i + 2..]),
+            Some(i) => format!("{}^-{}", &d[..i], &d[i + 1..]),
+            None => format!("{}^-1", d),
+        }).collect::<Vec<_>>().join("*"),
+        (_, 1) => format!("{}/{}", num.join("*"), den[0]),
+        (_, _) => format!("{}/({})", num.join("*"), den.join("*")),
+    }
+}
+
 impl fmt::Display for Unit {
-    /// Display a unit as a string (separates numerator and denominator)
+    /// Display a unit as a string (separates numerator and denominator). Base units are
+    /// always written in the same order: kg, m, s, A, K, mol, cd, B.
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match u_hash(self).ok().and_then(|a| LOOKUP.get(&a)) {
             Some(a) => write!(f, "{}", a),
-            None => {
-                let mut num = String::new();
-                let mut den = String::new();
-                fmt_unit!(self.kg, "kg", num, den);
-                fmt_unit!(self.m, "m", num, den);
-                fmt_unit!(self.s, "s", num, den);
-                fmt_unit!(self.a, "A", num, den);
-                fmt_unit!(self.mol, "mol", num, den);
-                fmt_unit!(self.k, "K", num, den);
-                fmt_unit!(self.cd, "cd", num, den);
-                match (num.is_empty(), den.is_empty()) {
-                    (true, true) => write!(f, ""),
-                    (true, false) => write!(f, "/ {}", den.trim_right()),
-                    (false, true) => write!(f, "{}", num.trim_right()),
-                    (false, false) => write!(f, "{}/ {}", num, den.trim_right()),
-                }
-            },
+            None => f.write_str(&write_unit(self, ["kg", "m", "s", "A", "K", "mol", "cd", "B"])),
         }
     }
 }
@@ -1,7 +1,14 @@
 //! Value with units. Ties together unit and value.
+//!
+//! All units are converted to SI base units as soon as they are evaluated, so a value only
+//! stores its dimensions: `2 ft * 3 m` is `1143/625 m^2` and `6 ft / (2 m)` is the unitless
+//! `1143/1250`. The unit system a result is shown in is decided by the calculator (see
+//! `units::simplify_units`).
 
 use unit::*;
 use value::*;
+use units;
+use numtheory;
 use rational::{OverflowError,AsFloat};
 use std::cmp;
 use std::ops::{Add,Sub,Mul,Div,Neg};
@@ -63,6 +70,72 @@ impl UnitValue {
         })
     }
     #[inline]
+    pub fn inexact(f: f64, reason: InexactReason) -> Result<UnitValue, ArithmeticError> {
+        Ok(UnitValue {
+            value: try!(Value::inexact(f, reason)),
+            unit: Unit::zero(),
+        })
+    }
+    #[inline]
+    pub fn inexact_reason(&self) -> Option<InexactReason> {
+        self.value.inexact_reason()
+    }
+    /// Create a value with the given unit (the value is in SI base units)
+    #[inline]
+    pub fn with_unit(value: Value, unit: Unit) -> UnitValue {
+        UnitValue {value: value, unit: unit}
+    }
+    /// Like `with_unit`, but checks that the value is a valid number, since a `Value` built
+    /// directly (e.g. `Value::Inexact(f64::NAN, ...)`) skips the checks in `Value::inexact`.
+    #[inline]
+    pub fn try_with_unit(value: Value, unit: Unit) -> Result<UnitValue, ArithmeticError> {
+        match value {
+            Value::Inexact(f, reason) => Ok(UnitValue::with_unit(try!(Value::inexact(f, reason)), unit)),
+            Value::Exact(_) => Ok(UnitValue::with_unit(value, unit)),
+        }
+    }
+    /// Helper for the constructors below: a number of the named unit
+    fn of_unit(f: f64, name: &str) -> Result<UnitValue, ArithmeticError> {
+        let unit = units::get(name).expect("unit not found");
+        UnitValue::from_input(f).and_then(|a| (&a).mul(&unit))
+    }
+    /// A length in meters
+    ///
+    /// ```
+    /// use ucalc::uval::UnitValue;
+    /// use ucalc::rational::AsFloat;
+    /// let v = UnitValue::meters(5.0).unwrap() / UnitValue::seconds(2.0).unwrap();
+    /// assert_eq!(v.value.as_float(), 2.5);
+    /// assert_eq!(format!("{}", v), "5/2 m/s");
+    /// ```
+    pub fn meters(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "m")
+    }
+    /// A mass in kilograms
+    pub fn kilograms(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "kg")
+    }
+    /// A time in seconds
+    pub fn seconds(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "s")
+    }
+    /// A current in amperes
+    pub fn amperes(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "A")
+    }
+    /// A temperature in kelvins
+    pub fn kelvins(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "K")
+    }
+    /// A luminous intensity in candelas
+    pub fn candelas(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "cd")
+    }
+    /// An amount of substance in moles
+    pub fn moles(f: f64) -> Result<UnitValue, ArithmeticError> {
+        UnitValue::of_unit(f, "mol")
+    }
+    #[inline]
     pub fn zero() -> UnitValue {
         UnitValue {value: Value::zero(), unit: Unit::zero()}
     }
@@ -84,6 +157,37 @@ impl UnitValue {
     pub fn unitless(&self) -> bool {
         self.unit == Unit::zero()
     }
+    /// How many of `target` make up this value (e.g. 5 km converted to m is 5000).
+    /// The dimensions must match, otherwise this is a UnitError.
+    pub fn convert_to(&self, target: &UnitValue) -> Result<Value, ArithmeticError> {
+        if self.unit != target.unit && !self.is_zero() {
+            return Err(ArithmeticError::UnitError)
+        }
+        (&self.value).div(&target.value)
+    }
+    /// Display this value as a number of the named unit (e.g. `1 kW` rather than `1000 W`).
+    /// Returns None if there is no such unit or it has different dimensions.
+    pub fn display_in(&self, name: &str, mode: DisplayMode, precision: Precision) -> Option<String> {
+        units::get(name).and_then(|unit| self.convert_to(&unit).ok()).map(|v| format!("{} {}", v.format_with(mode, precision), name))
+    }
+    /// Format the value using the given display mode, followed by the unit (unless unitless)
+    pub fn format(&self, mode: DisplayMode) -> String {
+        self.format_with(mode, Precision::default())
+    }
+    /// Format the value like `format`, rounding decimals to the given precision (see
+    /// `Value::format_with`)
+    pub fn format_with(&self, mode: DisplayMode, precision: Precision) -> String {
+        if self.unitless() {
+            self.value.format_with(mode, precision)
+        } else {
+            format!("{} {}", self.value.format_with(mode, precision), self.unit)
+        }
+    }
+    /// Compare any two values, first by unit, then by value. Unlike partial_cmp, this does not
+    /// require the units to be the same, so it can be used to sort values with different units.
+    pub fn total_cmp(&self, other: &UnitValue) -> cmp::Ordering {
+        self.unit.cmp(&other.unit).then(self.value.cmp(&other.value))
+    }
     pub fn add(&self, other: &UnitValue) -> Result<UnitValue, ArithmeticError> {
         // check that units correspond
         if self.unit == other.unit {
@@ -112,7 +216,7 @@ impl UnitValue {
         } else {
             // check for zero
             if self.is_zero() {
-                return Ok(other.clone())
+                return Ok(-*other)
             }
             if other.is_zero() {
                 return Ok(self.clone())
@@ -132,6 +236,13 @@ impl UnitValue {
             unit: try!((&self.unit).sub(&other.unit)),
         })
     }
+    /// n! for a unitless integer n (see `numtheory::factorial`)
+    pub fn factorial(&self) -> Result<UnitValue, ArithmeticError> {
+        if !self.unitless() {
+            return Err(ArithmeticError::UnitError)
+        }
+        numtheory::integer(self).and_then(numtheory::factorial).map(|v| UnitValue::with_unit(v, Unit::zero()))
+    }
     pub fn pow(&self, other: &UnitValue) -> Result<UnitValue, ArithmeticError> {
         if other.unitless() {
             if self.unitless() {
@@ -194,6 +305,21 @@ impl Neg for UnitValue {
         }
     }
 }
+/// Check whether two values have the same dimensions and differ by less than `tol`
+/// (in SI base units, so values written in different units can be compared).
+///
+/// ```
+/// use ucalc::units;
+/// use ucalc::uval::{UnitValue, approx_eq};
+/// let a = UnitValue::meters(1.0).unwrap();
+/// let b = UnitValue::from_input(100.0001).unwrap() * units::get("cm").unwrap();
+/// assert!(approx_eq(&a, &b, 1e-5));
+/// assert!(!approx_eq(&a, &b, 1e-7));
+/// ```
+pub fn approx_eq(a: &UnitValue, b: &UnitValue, tol: f64) -> bool {
+    (a.unit == b.unit || a.is_zero() || b.is_zero()) && (a.value.as_float() - b.value.as_float()).abs() < tol
+}
+
 
 impl fmt::Display for UnitValue {
     /// Display value followed by unit (unless unitless)
@@ -205,3 +331,119 @@ impl fmt::Display for UnitValue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use units;
+    use rational::AsFloat;
+
+    #[test]
+    fn test_constructors() {
+        let v = UnitValue::meters(5.0).unwrap() / UnitValue::seconds(2.0).unwrap();
+        assert_eq!(v.value.as_float(), 2.5);
+        assert_eq!(v.unit, units::get("m").unwrap().unit - units::get("s").unwrap().unit);
+        assert_eq!(format!("{}", v), "5/2 m/s");
+        assert_eq!(UnitValue::kilograms(1.0).unwrap(), units::get("kg").unwrap());
+        assert_eq!(UnitValue::with_unit(Value::from_input(3.0).unwrap(), units::get("A").unwrap().unit),
+                   UnitValue::amperes(3.0).unwrap());
+        assert!(UnitValue::moles(::std::f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_try_with_unit() {
+        let unit = units::get("A").unwrap().unit;
+        assert_eq!(UnitValue::try_with_unit(Value::from_input(3.0).unwrap(), unit), UnitValue::amperes(3.0));
+        assert!(UnitValue::try_with_unit(Value::from_float(2.5).unwrap(), unit).is_ok());
+        assert_eq!(UnitValue::try_with_unit(Value::Inexact(::std::f64::NAN, InexactReason::ExplicitFloat), unit),
+                   Err(ArithmeticError::DomainError));
+        assert_eq!(UnitValue::try_with_unit(Value::Inexact(::std::f64::INFINITY, InexactReason::Overflow), unit),
+                   Err(ArithmeticError::OverflowError));
+    }
+
+    #[test]
+    fn test_pow_units() {
+        let two = UnitValue::from_input(2.0).unwrap();
+        let zero = UnitValue::from_input(0.0).unwrap();
+        // an exponent with units is an error, even if it is zero
+        assert_eq!(two.pow(&UnitValue::meters(0.0).unwrap()), Err(ArithmeticError::UnitError));
+        assert_eq!(two.pow(&UnitValue::meters(1.0).unwrap()), Err(ArithmeticError::UnitError));
+        // a unitless zero exponent cancels the units
+        let one = UnitValue::meters(2.0).unwrap().pow(&zero).unwrap();
+        assert!(one.unitless());
+        assert_eq!(one, UnitValue::from_input(1.0).unwrap());
+    }
+
+    #[test]
+    fn test_display_order() {
+        let (kg, m, s) = (UnitValue::kilograms(1.0).unwrap(), UnitValue::meters(1.0).unwrap(), UnitValue::seconds(1.0).unwrap());
+        // momentum, however it is calculated
+        assert_eq!(format!("{}", kg * m / s), "1 kg*m/s");
+        assert_eq!(format!("{}", m / s * kg), "1 kg*m/s");
+        assert_eq!(format!("{}", (s / m / kg).pow(&UnitValue::from_input(-1.0).unwrap()).unwrap()), "1 kg*m/s");
+        let other = UnitValue::moles(1.0).unwrap() * UnitValue::kelvins(1.0).unwrap() * UnitValue::amperes(1.0).unwrap();
+        assert_eq!(format!("{}", other * m), "1 m*A*K*mol");
+        assert_eq!(format!("{}", m / other), "1 m/(A*K*mol)");
+    }
+
+    #[test]
+    fn test_display_compound() {
+        let (kg, m, s) = (UnitValue::kilograms(1.0).unwrap(), UnitValue::meters(1.0).unwrap(), UnitValue::seconds(1.0).unwrap());
+        // exponents of 1 are omitted, and negative exponents go after the slash (or stay
+        // negative if there is nothing before it)
+        assert_eq!(format!("{}", m / s), "1 m/s");
+        assert_eq!(format!("{}", m / s / s), "1 m/s^2");
+        assert_eq!(format!("{}", m * m), "1 m^2");
+        assert_eq!(format!("{}", kg / (m * m * m)), "1 kg/m^3");
+        assert_eq!(format!("{}", m * m / (s * s * s) / kg), "1 m^2/(kg*s^3)");
+        assert_eq!(format!("{}", s / m / m), "1 s/m^2");
+        assert_eq!(format!("{}", kg / kg / s / m), "1 m^-1*s^-1");
+        assert_eq!(format!("{}", m.pow(&UnitValue::from_input(0.5).unwrap()).unwrap()), "1 m^(1/2)");
+        assert_eq!(format!("{}", m.pow(&UnitValue::from_input(-0.5).unwrap()).unwrap()), "1 m^(-1/2)");
+        // exponents of 0 are dropped
+        assert_eq!(format!("{}", m * s / m), "1 s");
+        assert_eq!(format!("{}", m / m), "1");
+        // derived units are shown by name
+        assert_eq!(format!("{}", kg * m / s / s), "1 N");
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let m = UnitValue::meters(1.0).unwrap();
+        let cm = UnitValue::from_input(100.0001).unwrap() * units::get("cm").unwrap();
+        assert!(approx_eq(&m, &cm, 1e-5));
+        assert!(approx_eq(&cm, &m, 1e-5));
+        assert!(!approx_eq(&m, &cm, 1e-7));
+        assert!(!approx_eq(&m, &UnitValue::seconds(1.0).unwrap(), 1.0));
+        assert!(approx_eq(&UnitValue::zero(), &UnitValue::meters(1e-9).unwrap(), 1e-6));
+    }
+
+    #[test]
+    fn test_convert_to() {
+        let km = units::get("km").unwrap();
+        let m = units::get("m").unwrap();
+        let five_km = UnitValue::from_input(5.0).unwrap() * km;
+        assert_eq!(five_km.convert_to(&m), Ok(Value::from_input(5000.0).unwrap()));
+        assert_eq!(five_km.convert_to(&units::get("mi").unwrap()).map(|v| v.get_exact().is_some()), Ok(true));
+        assert_eq!(five_km.convert_to(&units::get("s").unwrap()), Err(ArithmeticError::UnitError));
+        assert_eq!(UnitValue::zero().convert_to(&m), Ok(Value::zero()));
+        // zero is compatible with any unit
+        assert_eq!(UnitValue::add(&UnitValue::zero(), &five_km), Ok(five_km));
+        assert_eq!(UnitValue::sub(&UnitValue::zero(), &five_km), Ok(-five_km));
+        assert_eq!(UnitValue::sub(&five_km, &UnitValue::zero()), Ok(five_km));
+        assert_eq!(units::scale("km"), Some(Value::from_input(1000.0).unwrap()));
+        assert_eq!(units::scale("nothing"), None);
+    }
+
+    #[test]
+    fn test_total_cmp() {
+        let m = |f| UnitValue::meters(f).unwrap();
+        let s = |f| UnitValue::seconds(f).unwrap();
+        let n = |f| UnitValue::from_input(f).unwrap();
+        let mut values = vec![s(3.0), m(2.0), n(5.0), m(1.0), s(-1.0), n(0.5)];
+        values.sort_by(UnitValue::total_cmp);
+        assert_eq!(values, vec![n(0.5), n(5.0), s(-1.0), s(3.0), m(1.0), m(2.0)]);
+        assert_eq!(m(1.0).partial_cmp(&s(1.0)), None);
+        assert_eq!(m(1.0).total_cmp(&m(1.0)), cmp::Ordering::Equal);
+    }
+}
@@ -7,21 +7,105 @@ use std::cmp::Ord;
 use std::ops::{Add,Sub,Mul,Div,Neg};
 use std::fmt;
 
+/// The reason a value is inexact
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum InexactReason {
+    /// The user typed a number that cannot be represented exactly
+    FloatInput,
+    /// An exact operation overflowed
+    Overflow,
+    /// The result of a function or power that is (usually) irrational
+    IrrationalFunction,
+    /// The value was given as a float (e.g. an inexact constant)
+    ExplicitFloat,
+    /// The denominator was larger than the maximum allowed
+    LargeDenominator,
+}
+
 /// Value type. A Value is either exact or inexact.
 /// All values are valid numbers and are not Infinity or NaN.
 #[derive(Copy, Clone, Debug)]
 pub enum Value {
-    /// An inexact (floating-point) value
-    Inexact(f64),
+    /// An inexact (floating-point) value, and why it is inexact
+    Inexact(f64, InexactReason),
     /// An exact (rational) value
     Exact(Rational),
 }
 
+/// How exact values are displayed (inexact values are always decimals, except in scientific
+/// mode)
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DisplayMode {
+    /// As a fraction, e.g. 1/6
+    Fraction,
+    /// As a whole number and a proper fraction, e.g. 3 1/2
+    Mixed,
+    /// As a (possibly rounded) decimal, e.g. 0.16666666666666666
+    Decimal,
+    /// As a decimal with the repeating digits in parentheses, e.g. 0.1(6)
+    Repeating,
+    /// In scientific notation, e.g. 1.5e3; an exact value whose decimal expansion does not
+    /// terminate is shown as a fraction instead
+    Scientific,
+}
+
+/// How many digits decimals are rounded to when they are displayed (as many as needed for
+/// each None). Exact values shown as fractions are never rounded.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct Precision {
+    /// Digits after the point of a plain decimal, e.g. 3.1416 with 4 (trailing zeros are
+    /// dropped, so 0.5 is still 0.5)
+    pub decimals: Option<usize>,
+    /// Significant digits in scientific notation, e.g. 1.23e-9 with 3
+    pub scientific: Option<usize>,
+    /// How a plain decimal halfway between two roundings is rounded, e.g. 0.125 to 2 digits
+    pub rounding: RoundingMode,
+}
+
+/// The most digits after the decimal point written in repeating mode
+const MAX_DECIMAL_DIGITS: usize = 20;
+
+/// Write a float in plain decimal notation between 1e-4 and 1e15, and in scientific notation
+/// outside that range.
+fn format_float(a: f64, precision: Precision) -> String {
+    if a != 0.0 && (a.abs() < 1e-4 || a.abs() >= 1e15) {
+        format_scientific(a, precision.scientific)
+    } else if let Some(n) = precision.decimals {
+        let rounded = format!("{:.*}", n, round_halfway(a, n, precision.rounding));
+        let rounded = if rounded.contains('.') { rounded.trim_right_matches('0').trim_right_matches('.') } else { &rounded[..] };
+        // e.g. -0.00001 to 4 decimals
+        if rounded == "-0" { "0".to_owned() } else { rounded.to_owned() }
+    } else {
+        format!("{}", a)
+    }
+}
+
+/// Round a float that is exactly halfway between two decimals with n digits after the point
+/// (formatting always rounds those to even); other floats are returned unchanged
+fn round_halfway(a: f64, n: usize, mode: RoundingMode) -> f64 {
+    // 10^n is only exact up to 10^22
+    if n > 22 { return a }
+    let scale = 10f64.powi(n as i32);
+    let scaled = a * scale;
+    // the product must be exact, or a is not really halfway
+    if scaled.fract().abs() != 0.5 || a.mul_add(scale, -scaled) != 0.0 { return a }
+    mode.round_float(scaled) / scale
+}
+
+/// Write a float in scientific notation with the given number of significant digits (e.g.
+/// 1.50e3 with 3), or as many as needed if None
+fn format_scientific(a: f64, digits: Option<usize>) -> String {
+    match digits {
+        Some(n) => format!("{:.*e}", n.max(1) - 1, a),
+        None => format!("{:e}", a),
+    }
+}
+
 impl AsFloat for Value {
     #[inline]
     fn as_float(&self) -> f64 {
         match self {
-            &Value::Inexact(a) => a,
+            &Value::Inexact(a, _) => a,
             &Value::Exact(ref a) => a.as_float(),
         }
     }
@@ -47,7 +131,7 @@ impl Ord for Value {
     fn cmp(&self, other: &Value) -> cmp::Ordering {
         match (self, other) {
             // compare two values (guaranteed to be non-NaN)
-            (&Value::Inexact(ref a), &Value::Inexact(ref b)) => a.partial_cmp(b).unwrap(),
+            (&Value::Inexact(ref a, _), &Value::Inexact(ref b, _)) => a.partial_cmp(b).unwrap(),
             // compare two exact values
             (&Value::Exact(ref a), &Value::Exact(ref b)) => a.cmp(b),
             // otherwise, convert one to float first
@@ -77,20 +161,47 @@ pub enum ArithmeticError {
 }
 
 impl Value {
-    /// Used for user input; approximates values that could be represented exactly (denominator 8)
+    /// Format using the given display mode
+    pub fn format(&self, mode: DisplayMode) -> String {
+        self.format_with(mode, Precision::default())
+    }
+    /// Format the value like `format`, but rounding decimals to the given precision (e.g.
+    /// 1.23e-9 with 3 significant digits in scientific notation). With a number of significant
+    /// digits, an exact value whose decimal expansion does not terminate is also rounded in
+    /// scientific mode, rather than shown as a fraction.
+    pub fn format_with(&self, mode: DisplayMode, precision: Precision) -> String {
+        match (self, mode) {
+            (&Value::Exact(ref a), DisplayMode::Decimal) => format_float(a.as_float(), precision),
+            (&Value::Exact(ref a), DisplayMode::Repeating) => a.to_decimal(MAX_DECIMAL_DIGITS),
+            (&Value::Exact(ref a), DisplayMode::Scientific) if precision.scientific.is_some()
+                => format_scientific(a.as_float(), precision.scientific),
+            (&Value::Exact(ref a), DisplayMode::Scientific) => a.to_scientific().unwrap_or_else(|| format!("{}", a)),
+            (&Value::Inexact(a, _), DisplayMode::Scientific) => format_scientific(a, precision.scientific),
+            (&Value::Inexact(a, _), _) => format_float(a, precision),
+            (&Value::Exact(ref a), DisplayMode::Mixed) => a.to_mixed_string(),
+            _ => format!("{}", self),
+        }
+    }
+    /// Used for user input; a number that can be written as a fraction with a power of 2 or
+    /// a power of 10 as the denominator (e.g. 0.1 = 1/10, 0.375 = 3/8) becomes exact, if it fits
+    /// in a Rational. Other numbers are inexact.
+    ///
+    /// So `268435456.125` (2147483649/8), `2147483648` and `0.000000001` (1/10^9) are exact,
+    /// but `0.0000000001` and `1e19` are inexact.
     pub fn from_input(f: f64) -> Result<Value, ArithmeticError> {
         if !f.is_nan() && !f.is_infinite() {
-            if (f * 8.0).fract() != 0.0 {
-                Ok(Value::Inexact(f))
-            } else {
-                let num = f * 8.0;
-                // if it can be represented exactly as a Rational, use that
-                if num.abs() > i32::max_value() as f64 {
-                    Ok(Value::Inexact(f))
-                } else {
-                    Rational::new(num as i32, 8).or(Err(ArithmeticError::DomainError)).map(Value::Exact)
+            // use the smallest denominator that gives back the same float; a decimal with up to
+            // 9 digits after the point (or a binary fraction) is the nearest float to n/d
+            let mut dens: Vec<i64> = (0..31).map(|a| 1 << a).chain((1..10).map(|b| 10i64.pow(b))).collect();
+            dens.sort();
+            for d in dens {
+                let num = (f * d as f64).round();
+                // i64::MAX rounds up to 2^63 as a float, so the comparison is strict
+                if num.abs() < i64::max_value() as f64 && num / d as f64 == f {
+                    return Rational::new_i64(num as i64, d).or(Err(ArithmeticError::DomainError)).map(Value::Exact)
                 }
             }
+            Ok(Value::Inexact(f, InexactReason::FloatInput))
         } else {
             // infinite values are overflow, NaN values are invalid
             if f.is_infinite() {
@@ -100,11 +211,74 @@ impl Value {
             }
         }
     }
+    /// The exact value of a decimal as the user typed it (e.g. `0.125` or `1.5e-3`, without
+    /// underscores). The digits are read directly rather than through a float, so any
+    /// terminating decimal is exact if its reduced fraction fits in a Rational: 3.14 is
+    /// 314/100 = 157/50. Otherwise this is an OverflowError (and for text that is not a
+    /// decimal, a DomainError).
+    pub fn from_decimal(text: &str) -> Result<Value, ArithmeticError> {
+        let (mantissa, exp) = match text.find(|c| c == 'e' || c == 'E') {
+            Some(i) => (&text[..i], try!(text[i + 1..].parse::<i32>().map_err(|_| ArithmeticError::DomainError))),
+            None => (text, 0),
+        };
+        let (int, frac) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+        if (int.is_empty() && frac.is_empty()) || !int.chars().chain(frac.chars()).all(|c| c.is_digit(10)) {
+            return Err(ArithmeticError::DomainError)
+        }
+        // the value is digits * 10^power; trailing zeros only change the power
+        let digits = format!("{}{}", int, frac);
+        let significant = digits.trim_right_matches('0');
+        let mut power = exp as i64 - frac.len() as i64 + (digits.len() - significant.len()) as i64;
+        let significant = significant.trim_left_matches('0');
+        if significant.is_empty() {
+            return Ok(Value::zero())
+        }
+        /// Multiply, failing if the result doesn't fit in a Rational
+        fn mul(a: i64, b: i64) -> Result<i64, ArithmeticError> {
+            a.checked_mul(b).ok_or(ArithmeticError::OverflowError)
+        }
+        let mut num: i64 = try!(significant.parse().map_err(|_| ArithmeticError::OverflowError));
+        let mut den = 1;
+        if power >= 0 {
+            while power > 0 {
+                num = try!(mul(num, 10));
+                power -= 1;
+            }
+        } else {
+            // 10^-power = 2^-power 5^-power, less the factors that cancel with the digits
+            let (mut twos, mut fives) = (-power, -power);
+            while twos > 0 && num % 2 == 0 {
+                num /= 2;
+                twos -= 1;
+            }
+            while fives > 0 && num % 5 == 0 {
+                num /= 5;
+                fives -= 1;
+            }
+            while twos > 0 {
+                den = try!(mul(den, 2));
+                twos -= 1;
+            }
+            while fives > 0 {
+                den = try!(mul(den, 5));
+                fives -= 1;
+            }
+        }
+        Ok(Value::Exact(try!(Rational::new_i64(num, den).map_err(ArithmeticError::from))))
+    }
     /// Convert a float into a Value, directly using the Inexact form. (Still checks for error)
     #[inline]
     pub fn from_float(f: f64) -> Result<Value, ArithmeticError> {
+        Value::inexact(f, InexactReason::ExplicitFloat)
+    }
+    /// Convert a float into an Inexact value for the given reason. (Still checks for error)
+    #[inline]
+    pub fn inexact(f: f64, reason: InexactReason) -> Result<Value, ArithmeticError> {
         if !f.is_nan() && !f.is_infinite() {
-            Ok(Value::Inexact(f))
+            Ok(Value::Inexact(f, reason))
         } else {
             if f.is_infinite() {
                 Err(ArithmeticError::OverflowError)
@@ -118,15 +292,51 @@ impl Value {
     pub fn get_exact(&self) -> Option<&Rational> {
         match self {
             &Value::Exact(ref a) => Some(a),
-            &Value::Inexact(_) => None,
+            &Value::Inexact(..) => None,
+        }
+    }
+    /// Get the reason this value is inexact (returns None if exact)
+    #[inline]
+    pub fn inexact_reason(&self) -> Option<InexactReason> {
+        match self {
+            &Value::Exact(_) => None,
+            &Value::Inexact(_, reason) => Some(reason),
+        }
+    }
+    /// The reason for an operation on self and other to be inexact (when they are not both exact)
+    #[inline]
+    fn combined_reason(&self, other: &Value) -> InexactReason {
+        self.inexact_reason().or(other.inexact_reason()).unwrap_or(InexactReason::Overflow)
+    }
+    /// Convert to an inexact value if the denominator is larger than `max`
+    #[inline]
+    pub fn limit_denominator(self, max: u32) -> Value {
+        match self {
+            Value::Exact(a) if a.den > max as u64 => Value::Inexact(a.as_float(), InexactReason::LargeDenominator),
+            a => a,
+        }
+    }
+    /// Round using the given function for exact or inexact values (an exact value stays exact)
+    pub fn round_with(&self, exact: fn(&Rational) -> Rational, inexact: fn(f64) -> f64) -> Value {
+        match self {
+            &Value::Exact(ref a) => Value::Exact(exact(a)),
+            &Value::Inexact(a, reason) => Value::Inexact(inexact(a), reason),
+        }
+    }
+    /// Round to the nearest integer, with halfway cases rounded according to `mode` (an exact
+    /// value stays exact)
+    pub fn round(&self, mode: RoundingMode) -> Value {
+        match self {
+            &Value::Exact(ref a) => Value::Exact(a.round_with_mode(mode)),
+            &Value::Inexact(a, reason) => Value::Inexact(mode.round_float(a), reason),
         }
     }
     /// Converts self into an integer if possible.
     #[inline]
     pub fn as_integer(&self) -> Option<i32> {
         match self {
-            &Value::Exact(ref a) => if a.is_integer() { Some(a.num) } else { None },
-            &Value::Inexact(a) => if a.fract() == 0.0 && a.abs() <= i32::max_value() as f64 { Some(a as i32) } else { None },
+            &Value::Exact(ref a) => if a.is_integer() && a.num.abs() <= i32::max_value() as i64 { Some(a.num as i32) } else { None },
+            &Value::Inexact(a, _) => if a.fract() == 0.0 && a.abs() <= i32::max_value() as f64 { Some(a as i32) } else { None },
         }
     }
     /// Zero value
@@ -134,33 +344,38 @@ impl Value {
     pub fn zero() -> Value {
         Value::Exact(Rational::zero())
     }
+    /// Check if negative
+    #[inline]
+    pub fn is_negative(&self) -> bool {
+        self.as_float() < 0.0
+    }
     /// Check if zero
     #[inline]
     pub fn is_zero(&self) -> bool {
         match self {
             &Value::Exact(ref a) => a.is_zero(),
-            &Value::Inexact(a) => a == 0.0,
+            &Value::Inexact(a, _) => a == 0.0,
         }
     }
     pub fn add(&self, other: &Value) -> Result<Value, ArithmeticError> {
         match (self.get_exact(), other.get_exact()) {
             // special case for two exact values
-            (Some(a), Some(b)) => a.add(b).map(Value::Exact).or_else(|_| Value::from_float(self.as_float() + other.as_float())),
-            _ => Value::from_float(self.as_float() + other.as_float())
+            (Some(a), Some(b)) => a.add(b).map(Value::Exact).or_else(|_| Value::inexact(self.as_float() + other.as_float(), InexactReason::Overflow)),
+            _ => Value::inexact(self.as_float() + other.as_float(), self.combined_reason(other))
         }
     }
     pub fn sub(&self, other: &Value) -> Result<Value, ArithmeticError> {
         match (self.get_exact(), other.get_exact()) {
             // special case for two exact values
-            (Some(a), Some(b)) => a.sub(b).map(Value::Exact).or_else(|_| Value::from_float(self.as_float() - other.as_float())),
-            _ => Value::from_float(self.as_float() - other.as_float())
+            (Some(a), Some(b)) => a.sub(b).map(Value::Exact).or_else(|_| Value::inexact(self.as_float() - other.as_float(), InexactReason::Overflow)),
+            _ => Value::inexact(self.as_float() - other.as_float(), self.combined_reason(other))
         }
     }
     pub fn mul(&self, other: &Value) -> Result<Value, ArithmeticError> {
         match (self.get_exact(), other.get_exact()) {
             // special case for two exact values'
-            (Some(a), Some(b)) => a.mul(b).map(Value::Exact).or_else(|_| Value::from_float(self.as_float() * other.as_float())),
-            _ => Value::from_float(self.as_float() * other.as_float())
+            (Some(a), Some(b)) => a.mul(b).map(Value::Exact).or_else(|_| Value::inexact(self.as_float() * other.as_float(), InexactReason::Overflow)),
+            _ => Value::inexact(self.as_float() * other.as_float(), self.combined_reason(other))
         }
     }
     pub fn div(&self, other: &Value) -> Result<Value, ArithmeticError> {
@@ -169,15 +384,36 @@ impl Value {
         }
         match (self.get_exact(), other.get_exact()) {
             // special case for two exact values
-            (Some(a), Some(b)) => a.div(b).map(Value::Exact).or_else(|_| Value::from_float(self.as_float() / other.as_float())),
-            _ => Value::from_float(self.as_float() / other.as_float())
+            (Some(a), Some(b)) => a.div(b).map(Value::Exact).or_else(|_| Value::inexact(self.as_float() / other.as_float(), InexactReason::Overflow)),
+            _ => Value::inexact(self.as_float() / other.as_float(), self.combined_reason(other))
         }
     }
     pub fn pow(&self, other: &Value) -> Result<Value, ArithmeticError> {
-        match self.get_exact() {
-            // if other is an integer, exponentiate rationally (unless overflow). otherwise, inexact
-            Some(a) => if let Some(e) = other.as_integer() { a.pow(e).map(Value::Exact).or_else(|_| Value::from_float(a.as_float().powi(e))) } else { Value::from_float(a.as_float().powf(other.as_float())) },
-            None => Value::from_float(self.as_float().powf(other.as_float()))
+        // if other is an integer, exponentiate rationally (unless overflow).
+        if let (Some(a), Some(e)) = (self.get_exact(), other.as_integer()) {
+            return a.pow(e).map(Value::Exact).or_else(|_| Value::inexact(a.as_float().powi(e), InexactReason::Overflow))
+        }
+        match other.get_exact() {
+            // a^(p/q) is the qth root of a, raised to the pth power
+            Some(e) => {
+                // even roots of negative numbers are not real
+                if self.is_negative() && e.den % 2 == 0 {
+                    return Err(ArithmeticError::DomainError)
+                }
+                // exact if the root is rational (and the exponent fits the argument types)
+                if e.den <= u32::max_value() as u64 && e.num.abs() <= i32::max_value() as i64 {
+                    if let Some(r) = self.get_exact().and_then(|a| a.root(e.den as u32)) {
+                        if let Ok(res) = r.pow(e.num as i32) {
+                            return Ok(Value::Exact(res))
+                        }
+                    }
+                }
+                // odd roots of negative numbers are negative (powf would give NaN)
+                let res = self.as_float().abs().powf(e.as_float());
+                let res = if self.is_negative() && e.num % 2 != 0 { -res } else { res };
+                Value::inexact(res, self.inexact_reason().unwrap_or(InexactReason::IrrationalFunction))
+            },
+            None => Value::inexact(self.as_float().powf(other.as_float()), self.combined_reason(other))
         }
     }
 }
@@ -216,16 +452,16 @@ impl Neg for Value {
     fn neg(self) -> Value {
         match self {
             Value::Exact(a) => Value::Exact(-a),
-            Value::Inexact(a) => Value::Inexact(-a),
+            Value::Inexact(a, reason) => Value::Inexact(-a, reason),
         }
     }
 }
 
-/// Format as inexact or exact
+/// Format as inexact or exact (see `format_float` for how inexact values are written)
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
-            &Value::Inexact(a) => write!(f, "{}", a),
+            &Value::Inexact(a, _) => write!(f, "{}", format_float(a, Precision::default())),
             &Value::Exact(ref a) => write!(f, "{}", a),
         }
     }
@@ -256,4 +492,197 @@ mod tests {
         assert_eq!(val!(V 4.0) * val!(V 1.0), val!(V 4.0));
         assert_eq!(val!(V 4.0) / val!(V 2.0), val!(V 2.0));
     }
+
+    #[test]
+    fn test_from_input() {
+        fn exact(f: f64) -> bool {
+            Value::from_input(f).unwrap().get_exact().is_some()
+        }
+        // denominator 8
+        assert!(exact(268435455.875));
+        assert!(exact(268435456.125));
+        assert!(exact(-268435456.125));
+        assert!(exact(4503599627370495.5));
+        // integers
+        assert!(exact(2147483648.0));
+        assert!(exact(-2147483648.0));
+        assert!(exact(9007199254740992.0));
+        assert!(!exact(9223372036854775808.0));
+        assert!(!exact(-9223372036854775808.0));
+        // decimals
+        assert_eq!(Value::from_input(0.1).unwrap().get_exact(), Some(&Rational::new(1, 10).unwrap()));
+        assert!(Value::from_float(0.1).unwrap().get_exact().is_none());
+        assert_eq!(Value::from_input(-2.75).unwrap().get_exact(), Some(&Rational::new(-11, 4).unwrap()));
+        assert!(exact(0.000000001));
+        assert!(!exact(0.0000000001));
+        assert!(exact(1.0 / 1073741824.0));
+        assert!(exact(2.71801));
+        assert!(!exact(::std::f64::consts::PI));
+        assert!(!exact(1.0 / 3.0));
+        // the value is preserved either way
+        for &f in &[268435455.875, 268435456.125, 1073741823.5, 9223372036854775808.0] {
+            assert_eq!(Value::from_input(f).unwrap().as_float(), f);
+        }
+    }
+
+    #[test]
+    fn test_from_decimal() {
+        let exact = |num, den| Ok(Value::Exact(Rational::new(num, den).unwrap()));
+        assert_eq!(Value::from_decimal("0.125"), exact(1, 8));
+        assert_eq!(Value::from_decimal("3.14"), exact(157, 50));
+        assert_eq!(Value::from_decimal("0.001"), exact(1, 1000));
+        assert_eq!(Value::from_decimal("0.1234567"), exact(1234567, 10000000));
+        assert_eq!(Value::from_decimal("00120.500"), exact(241, 2));
+        assert_eq!(Value::from_decimal(".5"), exact(1, 2));
+        assert_eq!(Value::from_decimal("7."), exact(7, 1));
+        assert_eq!(Value::from_decimal("1.5e-3"), exact(3, 2000));
+        assert_eq!(Value::from_decimal("2.5E3"), exact(2500, 1));
+        assert_eq!(Value::from_decimal("0.0e99"), exact(0, 1));
+        // the numerator and denominator only need to fit after reducing
+        assert_eq!(Value::from_decimal("268435455.875"), exact(2147483647, 8));
+        assert_eq!(Value::from_decimal("2147483647"), exact(2147483647, 1));
+        assert_eq!(Value::from_decimal("3000000000"), Ok(Value::Exact(Rational::from_i64(3000000000).unwrap())));
+        assert_eq!(Value::from_decimal("0.0000000001"), Ok(Value::Exact(Rational::new_i64(1, 10000000000).unwrap())));
+        assert_eq!(Value::from_decimal("9223372036854775807"), Ok(Value::Exact(Rational::from_i64(9223372036854775807).unwrap())));
+        assert_eq!(Value::from_decimal("9223372036854775808"), Err(ArithmeticError::OverflowError));
+        assert_eq!(Value::from_decimal("0.0000000000000000001"), Err(ArithmeticError::OverflowError));
+        assert_eq!(Value::from_decimal("1e999999999"), Err(ArithmeticError::OverflowError));
+        assert_eq!(Value::from_decimal("1e-999999999"), Err(ArithmeticError::OverflowError));
+        assert_eq!(Value::from_decimal("123456789012345678901234567890"), Err(ArithmeticError::OverflowError));
+        for bad in &["", ".", "1.2.3", "e5", "1e", "-1", "1_000"] {
+            assert_eq!(Value::from_decimal(bad), Err(ArithmeticError::DomainError), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_limit_denominator() {
+        let third = Value::Exact(Rational::new(1, 3).unwrap());
+        assert!(third.limit_denominator(3).get_exact().is_some());
+        assert!(third.limit_denominator(2).get_exact().is_none());
+        assert_eq!(third.limit_denominator(2), third);
+        assert!(val!(V 5.0).limit_denominator(1).get_exact().is_some());
+    }
+
+    #[test]
+    fn test_negative_roots() {
+        let third = Value::Exact(Rational::new(1, 3).unwrap());
+        let two_thirds = Value::Exact(Rational::new(2, 3).unwrap());
+        let half = Value::Exact(Rational::new(1, 2).unwrap());
+        assert_eq!(val!(V -8.0).pow(&third), Ok(val!(V -2.0)));
+        assert!(val!(V -8.0).pow(&third).unwrap().get_exact().is_some());
+        assert_eq!(val!(V -8.0).pow(&two_thirds), Ok(val!(V 4.0)));
+        assert_eq!(val!(V -4.0).pow(&half), Err(ArithmeticError::DomainError));
+        assert_eq!(val!(V -0.125).pow(&Value::Exact(Rational::new(-1, 3).unwrap())), Ok(val!(V -2.0)));
+        // roots that are not rational are inexact, but still real
+        let res = val!(V -2.0).pow(&third).unwrap();
+        assert!(res.get_exact().is_none());
+        assert!((res.as_float() + 2f64.cbrt()).abs() < 1e-12);
+        let res = Value::from_float(-8.0).unwrap().pow(&two_thirds).unwrap();
+        assert!((res.as_float() - 4.0).abs() < 1e-12);
+        // inexact exponents use powf
+        assert_eq!(val!(V -8.0).pow(&Value::from_float(0.5).unwrap()), Err(ArithmeticError::DomainError));
+    }
+
+    #[test]
+    fn test_display() {
+        let inexact = |a| format!("{}", Value::Inexact(a, InexactReason::ExplicitFloat));
+        assert_eq!(inexact(0.0001), "0.0001");
+        assert_eq!(inexact(1000000.0), "1000000");
+        assert_eq!(inexact(-1000000.0), "-1000000");
+        assert_eq!(inexact(0.0), "0");
+        assert_eq!(inexact(1e-10), "1e-10");
+        assert_eq!(inexact(1e20), "1e20");
+        assert_eq!(inexact(-2.5e20), "-2.5e20");
+    }
+
+    #[test]
+    fn test_display_modes() {
+        let third = Value::Exact(Rational::new(1, 3).unwrap());
+        assert_eq!(third.format(DisplayMode::Fraction), "1/3");
+        assert_eq!(third.format(DisplayMode::Decimal), "0.3333333333333333");
+        assert_eq!(third.format(DisplayMode::Repeating), "0.(3)");
+        assert_eq!(third.format(DisplayMode::Mixed), "1/3");
+        assert_eq!(Value::Exact(Rational::new(-7, 2).unwrap()).format(DisplayMode::Mixed), "-3 1/2");
+        assert_eq!(val!(V 3.5).format(DisplayMode::Mixed), "3 1/2");
+        assert_eq!(val!(V 3.0).format(DisplayMode::Mixed), "3");
+        let sixth = Value::Exact(Rational::new(1, 6).unwrap());
+        assert_eq!(sixth.format(DisplayMode::Repeating), "0.1(6)");
+        assert_eq!(val!(V 3.5).format(DisplayMode::Repeating), "3.5");
+        assert_eq!(val!(V 3.5).format(DisplayMode::Decimal), "3.5");
+        // inexact values are always decimals
+        assert_eq!(Value::Inexact(0.5, InexactReason::ExplicitFloat).format(DisplayMode::Repeating), "0.5");
+        assert_eq!(val!(V 1500.0).format(DisplayMode::Scientific), "1.5e3");
+        assert_eq!(third.format(DisplayMode::Scientific), "1/3");
+        assert_eq!(Value::Inexact(1500.0, InexactReason::ExplicitFloat).format(DisplayMode::Scientific), "1.5e3");
+        assert_eq!(Value::Inexact(0.1, InexactReason::ExplicitFloat).format(DisplayMode::Scientific), "1e-1");
+    }
+
+    #[test]
+    fn test_format_digits() {
+        let inexact = |a| Value::Inexact(a, InexactReason::ExplicitFloat);
+        let sci = |n| Precision { scientific: Some(n), ..Precision::default() };
+        // only values outside [1e-4, 1e15) are in scientific notation
+        assert_eq!(inexact(1.23456e-9).format_with(DisplayMode::Fraction, sci(3)), "1.23e-9");
+        assert_eq!(inexact(0.0001).format_with(DisplayMode::Fraction, sci(3)), "0.0001");
+        assert_eq!(inexact(0.0000999999).format_with(DisplayMode::Fraction, sci(3)), "1.00e-4");
+        assert_eq!(inexact(999999999999999.0).format_with(DisplayMode::Fraction, sci(3)), "999999999999999");
+        assert_eq!(inexact(1e15).format_with(DisplayMode::Fraction, sci(3)), "1.00e15");
+        assert_eq!(inexact(-2.5e20).format_with(DisplayMode::Decimal, sci(1)), "-2e20");
+        assert_eq!(inexact(0.0).format_with(DisplayMode::Fraction, sci(3)), "0");
+        assert_eq!(inexact(1.23456e-9).format_with(DisplayMode::Fraction, Precision::default()), "1.23456e-9");
+        // in scientific mode, everything is
+        assert_eq!(inexact(1500.0).format_with(DisplayMode::Scientific, sci(3)), "1.50e3");
+        assert_eq!(val!(V 1500.0).format_with(DisplayMode::Scientific, sci(2)), "1.5e3");
+        let third = Value::Exact(Rational::new(1, 3).unwrap());
+        assert_eq!(third.format_with(DisplayMode::Scientific, sci(4)), "3.333e-1");
+        assert_eq!(third.format_with(DisplayMode::Fraction, sci(4)), "1/3");
+        assert_eq!(Value::Exact(Rational::new(1, 1000000).unwrap()).format_with(DisplayMode::Decimal, sci(2)), "1.0e-6");
+    }
+
+    #[test]
+    fn test_format_decimals() {
+        let inexact = |a| Value::Inexact(a, InexactReason::ExplicitFloat);
+        let decimals = |n| Precision { decimals: Some(n), ..Precision::default() };
+        let pi = inexact(::std::f64::consts::PI);
+        assert_eq!(pi.format_with(DisplayMode::Fraction, decimals(4)), "3.1416");
+        assert_eq!(pi.format_with(DisplayMode::Fraction, decimals(2)), "3.14");
+        assert_eq!(pi.format_with(DisplayMode::Fraction, decimals(0)), "3");
+        assert_eq!(pi.format_with(DisplayMode::Fraction, Precision::default()), "3.141592653589793");
+        assert_eq!(inexact(0.5).format_with(DisplayMode::Fraction, decimals(4)), "0.5");
+        assert_eq!(inexact(-2.0).format_with(DisplayMode::Fraction, decimals(4)), "-2");
+        assert_eq!(inexact(-0.0004).format_with(DisplayMode::Fraction, decimals(2)), "0");
+        assert_eq!(inexact(1234.5678).format_with(DisplayMode::Fraction, decimals(1)), "1234.6");
+        // halfway cases are rounded with the rounding mode
+        let rounded = |a, mode| inexact(a).format_with(DisplayMode::Fraction,
+                                                       Precision { rounding: mode, ..decimals(2) });
+        assert_eq!(rounded(0.125, RoundingMode::HalfEven), "0.12");
+        assert_eq!(rounded(0.125, RoundingMode::HalfUp), "0.13");
+        assert_eq!(rounded(-0.125, RoundingMode::HalfUp), "-0.12");
+        assert_eq!(rounded(-0.125, RoundingMode::HalfAway), "-0.13");
+        // 1.005 is really 1.00499999999999989...
+        assert_eq!(rounded(1.005, RoundingMode::HalfAway), "1");
+        // numbers in scientific notation use the other setting
+        assert_eq!(inexact(1.23456e-9).format_with(DisplayMode::Fraction, decimals(2)), "1.23456e-9");
+        // exact fractions are not rounded, but exact decimals are
+        let third = Value::Exact(Rational::new(1, 3).unwrap());
+        assert_eq!(third.format_with(DisplayMode::Fraction, decimals(2)), "1/3");
+        assert_eq!(third.format_with(DisplayMode::Repeating, decimals(2)), "0.(3)");
+        assert_eq!(third.format_with(DisplayMode::Decimal, decimals(2)), "0.33");
+    }
+
+    #[test]
+    fn test_inexact_reason() {
+        let big = Value::Exact(Rational::new(1, 65536).unwrap());
+        assert_eq!(val!(V 0.5).inexact_reason(), None);
+        assert_eq!(val!(V 0.1234567891).inexact_reason(), Some(InexactReason::FloatInput));
+        assert_eq!(Value::from_float(0.5).unwrap().inexact_reason(), Some(InexactReason::ExplicitFloat));
+        let cube = big.pow(&val!(V 3.0)).unwrap();
+        assert_eq!(cube.inexact_reason(), None);
+        assert_eq!((&cube).mul(&cube).unwrap().inexact_reason(), Some(InexactReason::Overflow));
+        assert_eq!(big.pow(&val!(V 4.0)).unwrap().inexact_reason(), Some(InexactReason::Overflow));
+        assert_eq!(val!(V 2.0).pow(&val!(V 0.5)).unwrap().inexact_reason(), Some(InexactReason::IrrationalFunction));
+        // the reason of an inexact operand is kept
+        assert_eq!((&val!(V 0.1234567891)).add(&big).unwrap().inexact_reason(), Some(InexactReason::FloatInput));
+        assert_eq!((-val!(V 0.1234567891)).inexact_reason(), Some(InexactReason::FloatInput));
+    }
 }
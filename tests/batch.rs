@@ -0,0 +1,135 @@
+//! Tests of batch mode, which run the calculator binary
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Run the calculator with the given arguments and input; return the exit code and output
+fn run(args: &[&str], input: &str) -> (i32, String) {
+    let (code, stdout, _) = run_with_stderr(args, input);
+    (code, stdout)
+}
+
+/// Run the calculator with the given arguments and input; return the exit code, output, and
+/// error output
+fn run_with_stderr(args: &[&str], input: &str) -> (i32, String, String) {
+    run_with_config(args, input, "")
+}
+
+/// Run the calculator with the given config file contents, arguments, and input; return the
+/// exit code, output, and error output
+fn run_with_config(args: &[&str], input: &str, config: &str) -> (i32, String, String) {
+    // each test gets its own config file, since tests run in parallel
+    let path = env::temp_dir().join(format!("ucalcrc-{}-{}", process::id(), CONFIGS.fetch_add(1, Ordering::SeqCst)));
+    fs::write(&path, config).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ucalc"))
+        .env("UCALC_CONFIG", &path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not run ucalc");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    (output.status.code().unwrap(), String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+/// The number of config files made so far
+static CONFIGS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn test_batch() {
+    let input = "1 + 1\n1/0\n2 + 2\n";
+    assert_eq!(run(&["--batch"], input), (0, "=> 2\n=> division by zero\n=> 4\n".to_owned()));
+    assert_eq!(run(&["--batch", "--fail-fast"], input), (1, "=> 2\n=> division by zero\n".to_owned()));
+    assert_eq!(run(&["--batch", "--fail-fast"], "1 + 1\n"), (0, "=> 2\n".to_owned()));
+}
+
+#[test]
+fn test_echo_names() {
+    let input = "area = 50 m^2\narea / 2\n";
+    assert_eq!(run(&["--batch", "--echo-names"], input), (0, "=> area = 50 m^2\n=> 25 m^2\n".to_owned()));
+    assert_eq!(run(&["--batch"], input), (0, "=> 50 m^2\n=> 25 m^2\n".to_owned()));
+}
+
+#[test]
+fn test_rpn() {
+    assert_eq!(run(&["--batch", "--rpn"], "3 4 +\n3 4 + 2 *\n3 +\n"), (0, "=> 7\n=> 14\nsyntax error\n".to_owned()));
+}
+
+#[test]
+fn test_one_shot() {
+    assert_eq!(run_with_stderr(&["-e", "2 + 2"], ""), (0, "4\n".to_owned(), String::new()));
+    assert_eq!(run_with_stderr(&["--expr", "5 km in m"], ""), (0, "5000 m\n".to_owned(), String::new()));
+    assert_eq!(run_with_stderr(&["-e", "factor 360"], ""), (0, "2^3 * 3^2 * 5\n".to_owned(), String::new()));
+    assert_eq!(run_with_stderr(&["--rpn", "-e", "3 4 +"], ""), (0, "7\n".to_owned(), String::new()));
+    assert_eq!(run_with_stderr(&["-e", "1/0"], ""), (1, String::new(), "division by zero\n".to_owned()));
+    assert_eq!(run_with_stderr(&["-e", "2 +"], ""), (1, String::new(), "syntax error\n".to_owned()));
+    // the other options apply as they do to any other input
+    assert_eq!(run_with_stderr(&["--error-codes", "-e", "1/0"], ""), (1, String::new(), "division by zero\nE_DIVZERO\n".to_owned()));
+    assert_eq!(run_with_stderr(&["--echo-names", "-e", "area = 50 m^2"], ""), (0, "area = 50 m^2\n".to_owned(), String::new()));
+    // the config file still applies
+    assert_eq!(run_with_config(&["-e", "7/2"], "", "display decimal\n"), (0, "3.5\n".to_owned(), String::new()));
+    // without -e, the argument is a script, even if there is no such file
+    let (code, stdout, stderr) = run_with_stderr(&["2 + 2"], "");
+    assert_eq!((code, &stdout[..]), (2, ""));
+    assert!(stderr.starts_with("could not open 2 + 2"), "{}", stderr);
+    assert_eq!(run_with_stderr(&["-e"], "").0, 2);
+}
+
+#[test]
+fn test_piped() {
+    // without --batch, piped input gives just the results
+    assert_eq!(run_with_stderr(&[], "3*4\n"), (0, "12\n".to_owned(), String::new()));
+    let input = "x = 2 m\nx / 0\nx^2\n";
+    assert_eq!(run_with_stderr(&[], input), (0, "2 m\n4 m^2\n".to_owned(), "division by zero\n".to_owned()));
+    assert_eq!(run_with_stderr(&["--fail-fast"], input), (1, "2 m\n".to_owned(), "division by zero\n".to_owned()));
+    // so are warnings
+    assert_eq!(run_with_stderr(&[], "65536(65536)(65536)(65536)\n"),
+               (0, "1.8446744073709552e19\n".to_owned(),
+                "warning: a multiplication overflowed, so the result is inexact\n".to_owned()));
+}
+
+#[test]
+fn test_error_codes() {
+    let input = "1/0\nasin(2)\n1 m + 1 s\n1 +\n2 + 2\n";
+    let (code, stdout, stderr) = run_with_stderr(&["--batch", "--error-codes"], input);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "=> division by zero\n=> argument out of domain\n=> incompatible units\nsyntax error\n=> 4\n");
+    assert_eq!(stderr, "E_DIVZERO\nE_DOMAIN\nE_UNIT\nE_SYNTAX\n");
+    // without the option, nothing is printed to stderr
+    assert_eq!(run_with_stderr(&["--batch"], input).2, "");
+}
+
+#[test]
+fn test_config() {
+    let (code, stdout, stderr) = run_with_config(&["--batch"], "sin(90)\n1/3\n", "# settings\nangles degrees\ndisplay decimal\n");
+    assert_eq!((code, &stdout[..], &stderr[..]), (0, "=> 1\n=> 0.3333333333333333\n", ""));
+    // invalid lines are reported, but the rest of the file still applies
+    let (_, stdout, stderr) = run_with_config(&["--batch"], "sin(90)\n", "locale fr\nangles degrees\n");
+    assert_eq!(stdout, "=> 1\n");
+    assert!(stderr.contains("line 1: unknown setting locale fr"), "{}", stderr);
+    // options override the config file
+    assert_eq!(run_with_config(&["--batch"], "1/3\n", "maxden 2\n").1, "=> 0.3333333333333333\n");
+    assert_eq!(run_with_config(&["--batch", "--max-denominator", "3"], "1/3\n", "maxden 2\n").1, "=> 1/3\n");
+}
+
+#[test]
+fn test_sig_figs() {
+    // values with units are rounded without a warning
+    assert_eq!(run(&["--batch"], "sigfigs on\n2.0 m * 3.0\n"), (0, "=> 6.0 m\n".to_owned()));
+}
+
+#[test]
+fn test_export() {
+    let path = env::temp_dir().join(format!("ucalc-export-{}.csv", process::id()));
+    let input = format!("5 m\nexport {}\n", path.display());
+    // values with units are exported without a warning
+    assert_eq!(run(&["--batch"], &input), (0, format!("=> 5 m\nwrote 1 results to {}\n", path.display())));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "input,value,unit,exact\n5 m,5,m,true\n");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,48 @@
+//! Tests of binding and using variables through the library
+
+extern crate ucalc;
+
+use ucalc::CalculatorError;
+use ucalc::calc::Calculator;
+use ucalc::uval::UnitValue;
+
+/// A unitless value
+fn value(a: f64) -> Result<UnitValue, CalculatorError> {
+    Ok(UnitValue::from_input(a).unwrap())
+}
+
+#[test]
+fn test_assignment() {
+    let mut calc = Calculator::new();
+    assert_eq!(calc.run("x = 3 + 4"), value(7.0));
+    assert_eq!(calc.run("x * 2"), value(14.0));
+    assert_eq!(calc.run("y=x/2"), value(3.5));
+    // reassigning overwrites the old value
+    assert_eq!(calc.run("x = 1"), value(1.0));
+    assert_eq!(calc.run("x + y"), value(4.5));
+    assert_eq!(calc.run("x := 10"), value(10.0));
+    assert_eq!(calc.run("x"), value(10.0));
+}
+
+#[test]
+fn test_assignment_units() {
+    let mut calc = Calculator::new();
+    let d = calc.run("d := 3 mi").unwrap();
+    assert_eq!(calc.format("d := 3 mi", &d), "3 mi");
+    // only `:=` remembers the unit
+    let dist = calc.run("dist = 3 mi").unwrap();
+    assert_eq!(calc.format("dist", &dist), "15840 ft");
+    let t = calc.run("t = d / (60 mi/hr)").unwrap();
+    assert_eq!(calc.format("t", &t), "180 s");
+}
+
+#[test]
+fn test_undefined() {
+    let mut calc = Calculator::new();
+    assert_eq!(calc.run("z + 1"), Err(CalculatorError::SyntaxError));
+    assert_eq!(calc.run("x = z"), Err(CalculatorError::SyntaxError));
+    assert_eq!(calc.run("x"), Err(CalculatorError::SyntaxError));
+    // units and constants can't be assigned to
+    assert_eq!(calc.run("m = 5"), Err(CalculatorError::SyntaxError));
+    assert_eq!(calc.run("x = "), Err(CalculatorError::SyntaxError));
+}